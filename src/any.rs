@@ -0,0 +1,111 @@
+
+use crate::{
+    HeteroSizedVec,
+    memory::Meta,
+};
+
+use core::{
+    alloc::Allocator,
+    any::Any,
+    ops::Range,
+    marker::PhantomData,
+    ptr,
+};
+use alloc::alloc::Global;
+
+/// Iterator over the elements of a `HeteroSizedVec<dyn Any>` which are of
+/// concrete type `C`.
+///
+/// Consecutive elements which share a vtable reuse the previous type check,
+/// so runs of the same type are filtered without dynamic calls.
+pub struct IterOf<'a, T: ?Sized, C, A: Allocator = Global> {
+    vec: &'a HeteroSizedVec<T, A>,
+    index: Range<usize>,
+    // vtables last found to be and not to be `C`
+    matched_meta: Option<Meta<T>>,
+    rejected_meta: Option<Meta<T>>,
+    p: PhantomData<fn() -> C>,
+}
+
+macro_rules! impl_any_downcast {
+    ($($t:ty),* $(,)?)=>{$(
+        impl<A: Allocator> HeteroSizedVec<$t, A> {
+            /// Get element by index as a reference to its concrete type.
+            ///
+            /// Returns `None` if out of bounds or not a `C`.
+            pub fn get_as<C: Any>(&self, index: usize) -> Option<&C> {
+                self.get(index)?.downcast_ref::<C>()
+            }
+
+            /// Get element by index as a mutable reference to its concrete
+            /// type.
+            ///
+            /// Returns `None` if out of bounds or not a `C`.
+            pub fn get_mut_as<C: Any>(&mut self, index: usize) -> Option<&mut C> {
+                self.get_mut(index)?.downcast_mut::<C>()
+            }
+
+            /// Iterate over the elements which are of concrete type `C`.
+            pub fn iter_of<C: Any>(&self) -> IterOf<'_, $t, C, A> {
+                IterOf {
+                    vec: self,
+                    index: 0..self.len(),
+                    matched_meta: None,
+                    rejected_meta: None,
+                    p: PhantomData,
+                }
+            }
+
+            /// Remove an element by index and move it out as its concrete
+            /// type.
+            ///
+            /// Returns `None`, leaving the vector unchanged, if out of
+            /// bounds or not a `C`.
+            pub fn remove_as<C: Any>(&mut self, index: usize) -> Option<C> {
+                unsafe {
+                    let elem: *const C = self.get_as::<C>(index)?;
+                    let elem: C = ptr::read(elem);
+                    self.forget(index);
+                    Some(elem)
+                }
+            }
+        }
+
+        impl<'a, C: Any, A: Allocator> Iterator for IterOf<'a, $t, C, A> {
+            type Item = &'a C;
+
+            fn next(&mut self) -> Option<&'a C> {
+                for i in &mut self.index {
+                    let meta: Meta<$t> = self.vec.ptr_meta[i];
+                    let elem: &'a $t = &self.vec[i];
+
+                    let is_match: bool =
+                        if self.matched_meta == Some(meta) {
+                            true
+                        } else if self.rejected_meta == Some(meta) {
+                            false
+                        } else if elem.is::<C>() {
+                            self.matched_meta = Some(meta);
+                            true
+                        } else {
+                            self.rejected_meta = Some(meta);
+                            false
+                        };
+
+                    if is_match {
+                        return Some(unsafe {
+                            &*(elem as *const $t as *const C)
+                        });
+                    }
+                }
+                None
+            }
+        }
+    )*};
+}
+
+impl_any_downcast!(
+    dyn Any,
+    dyn Any + Send,
+    dyn Any + Send + Sync,
+);
@@ -0,0 +1,321 @@
+
+use crate::{
+    pushable::{
+        HeteroSizedPush,
+        InPlace,
+    },
+    memory::{
+        Meta,
+        DropHandler,
+        align_up,
+        ptr_into_parts,
+        ptr_from_parts,
+    },
+};
+
+use core::{
+    ops::{
+        Index,
+        IndexMut,
+        Range,
+    },
+    mem::{
+        ManuallyDrop,
+        MaybeUninit,
+    },
+    marker::{
+        PhantomData,
+        Unsize,
+    },
+    ptr,
+    fmt::{
+        self,
+        Debug,
+        Formatter,
+    },
+};
+
+/// Alignment of inline storage.
+///
+/// Elements with greater alignment than this can't be stored inline.
+pub(crate) const INLINE_ALIGN: usize = 16;
+
+/// Inline, uninitialized bytes, aligned to `INLINE_ALIGN`.
+// this must be kept in sync with `INLINE_ALIGN`
+#[repr(C, align(16))]
+pub(crate) struct InlineBytes<const BYTES: usize>([MaybeUninit<u8>; BYTES]);
+
+impl<const BYTES: usize> InlineBytes<BYTES> {
+    pub(crate) fn new() -> Self {
+        InlineBytes([MaybeUninit::uninit(); BYTES])
+    }
+
+    pub(crate) fn as_ptr(&self) -> *const u8 {
+        self.0.as_ptr() as *const u8
+    }
+
+    pub(crate) fn as_mut_ptr(&mut self) -> *mut u8 {
+        self.0.as_mut_ptr() as *mut u8
+    }
+}
+
+/// Fixed-capacity vector of an unsized type, which never allocates.
+///
+/// Elements are stored inline in up to `BYTES` bytes, and there may be up to
+/// `N` of them. Elements may be aligned to at most 16 bytes.
+pub struct HeteroSizedArrayVec<T: ?Sized, const BYTES: usize, const N: usize> {
+    // densely packed elements
+    // respects alignment rules
+    storage: InlineBytes<BYTES>,
+    // number of bytes of storage in use
+    storage_len: usize,
+    // fat-pointer metadata for each element
+    ptr_meta: [MaybeUninit<Meta<T>>; N],
+    // start-indices of each element within storage
+    mem_indices: [usize; N],
+    // handlers for dropping each element, if it has a destructor
+    drop_handlers: [Option<DropHandler<T>>; N],
+    len: usize,
+
+    p: PhantomData<T>,
+}
+
+impl<T: ?Sized, const BYTES: usize, const N: usize> HeteroSizedArrayVec<T, BYTES, N> {
+    /// New, empty vector.
+    pub fn new() -> Self {
+        HeteroSizedArrayVec {
+            storage: InlineBytes::new(),
+            storage_len: 0,
+            ptr_meta: [MaybeUninit::uninit(); N],
+            mem_indices: [0; N],
+            drop_handlers: [None; N],
+            len: 0,
+
+            p: PhantomData,
+        }
+    }
+
+    /// Length in elements.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether there are no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of bytes of storage in use, including padding.
+    pub fn bytes_len(&self) -> usize {
+        self.storage_len
+    }
+
+    /// Try to push an element onto the vector.
+    ///
+    /// Gives the element back if it doesn't fit.
+    pub fn try_push<E: HeteroSizedPush<T>>(&mut self, elem: E) -> Result<(), E> {
+        unsafe {
+            let elem_size: usize = elem.elem_size();
+            let elem_align: usize = elem.elem_align();
+            let offset: usize = align_up(self.storage_len, elem_align);
+
+            if
+                self.len == N
+                || elem_align > INLINE_ALIGN
+                || offset + elem_size > BYTES
+            {
+                return Err(elem);
+            }
+
+            // prevent double-free in panic
+            let mut elem = ManuallyDrop::new(elem);
+
+            let (elem_data_ptr, meta) = ptr_into_parts::<T>(elem.elem_ptr());
+            ptr::copy_nonoverlapping::<u8>(
+                // src:
+                elem_data_ptr,
+                // dst:
+                self.storage.as_mut_ptr().add(offset),
+                // len:
+                elem_size,
+            );
+            self.storage_len = offset + elem_size;
+
+            self.ptr_meta[self.len] = MaybeUninit::new(meta);
+            self.mem_indices[self.len] = offset;
+            self.drop_handlers[self.len] = elem.elem_drop_handler();
+
+            elem.outer_drop();
+
+            self.len += 1;
+            Ok(())
+        }
+    }
+
+    /// Try to push some value which unsizes to the element type.
+    ///
+    /// Gives the value back if it doesn't fit.
+    pub fn try_push_value<E: Unsize<T>>(&mut self, elem: E) -> Result<(), E> {
+        self.try_push(InPlace(elem)).map_err(|InPlace(elem)| elem)
+    }
+
+    /// Get element by index as reference.
+    pub fn get(&self, index: usize) -> Option<&T> {
+        if index >= self.len {
+            return None;
+        }
+        unsafe {
+            let data: *const u8 = self.storage.as_ptr()
+                .add(self.mem_indices[index]);
+            Some(&*ptr_from_parts::<T>(data as *mut u8, self.ptr_meta[index].assume_init()))
+        }
+    }
+
+    /// Get element by index as mutable reference.
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        if index >= self.len {
+            return None;
+        }
+        unsafe {
+            let data: *mut u8 = self.storage.as_mut_ptr()
+                .add(self.mem_indices[index]);
+            Some(&mut *ptr_from_parts::<T>(data, self.ptr_meta[index].assume_init()))
+        }
+    }
+
+    /// Give up ownership of all elements, without dropping them, and return
+    /// how many there were.
+    ///
+    /// They remain readable through `raw_elem` until the next push.
+    pub(crate) unsafe fn forget_all(&mut self) -> usize {
+        self.storage_len = 0;
+        let len = self.len;
+        self.len = 0;
+        len
+    }
+
+    /// Pointer to and drop handler of an element, without checking that
+    /// it's still owned.
+    pub(crate) unsafe fn raw_elem(&mut self, index: usize) -> (*mut T, Option<DropHandler<T>>) {
+        let data: *mut u8 = self.storage.as_mut_ptr()
+            .add(self.mem_indices[index]);
+        (
+            ptr_from_parts::<T>(data, self.ptr_meta[index].assume_init()),
+            self.drop_handlers[index],
+        )
+    }
+
+    pub fn iter(&self) -> ArrayVecIter<'_, T, BYTES, N> {
+        ArrayVecIter {
+            vec: self,
+            index: 0..self.len,
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> ArrayVecIterMut<'_, T, BYTES, N> {
+        ArrayVecIterMut {
+            index: 0..self.len,
+            vec: self,
+        }
+    }
+}
+
+impl<T: ?Sized, const BYTES: usize, const N: usize> Default for HeteroSizedArrayVec<T, BYTES, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: ?Sized, const BYTES: usize, const N: usize> Drop for HeteroSizedArrayVec<T, BYTES, N> {
+    fn drop(&mut self) {
+        unsafe {
+            for i in 0..self.len {
+                if let Some(destructor) = self.drop_handlers[i] {
+                    let ptr: *mut u8 = self.storage.as_mut_ptr()
+                        .add(self.mem_indices[i]);
+                    destructor(ptr, self.ptr_meta[i].assume_init());
+                }
+            }
+        }
+    }
+}
+
+// index operator
+
+impl<T: ?Sized, const BYTES: usize, const N: usize> Index<usize> for HeteroSizedArrayVec<T, BYTES, N> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        self.get(index).unwrap()
+    }
+}
+
+impl<T: ?Sized, const BYTES: usize, const N: usize> IndexMut<usize> for HeteroSizedArrayVec<T, BYTES, N> {
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        self.get_mut(index).unwrap()
+    }
+}
+
+// iterators
+
+pub struct ArrayVecIter<'a, T: ?Sized, const BYTES: usize, const N: usize> {
+    vec: &'a HeteroSizedArrayVec<T, BYTES, N>,
+    index: Range<usize>,
+}
+
+impl<'a, T: ?Sized, const BYTES: usize, const N: usize> Iterator for ArrayVecIter<'a, T, BYTES, N> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.index.next()
+            .map(|i| &self.vec[i])
+    }
+}
+
+pub struct ArrayVecIterMut<'a, T: ?Sized, const BYTES: usize, const N: usize> {
+    vec: &'a mut HeteroSizedArrayVec<T, BYTES, N>,
+    index: Range<usize>,
+}
+
+impl<'a, T: ?Sized, const BYTES: usize, const N: usize> Iterator for ArrayVecIterMut<'a, T, BYTES, N> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.index.next()
+            .map(|i| unsafe {
+                // each index is visited once
+                &mut *(&mut self.vec[i] as *mut T)
+            })
+    }
+}
+
+impl<'a, T: ?Sized, const BYTES: usize, const N: usize> IntoIterator for &'a HeteroSizedArrayVec<T, BYTES, N> {
+    type Item = &'a T;
+    type IntoIter = ArrayVecIter<'a, T, BYTES, N>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, T: ?Sized, const BYTES: usize, const N: usize> IntoIterator for &'a mut HeteroSizedArrayVec<T, BYTES, N> {
+    type Item = &'a mut T;
+    type IntoIter = ArrayVecIterMut<'a, T, BYTES, N>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+// debug
+
+impl<T: ?Sized, const BYTES: usize, const N: usize> Debug for HeteroSizedArrayVec<T, BYTES, N>
+where
+    for<'a> &'a T: Debug
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_list()
+            .entries(self)
+            .finish()
+    }
+}
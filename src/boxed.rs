@@ -0,0 +1,216 @@
+
+use crate::{
+    pushable::{
+        HeteroSizedPush,
+        InPlace,
+    },
+    memory::{
+        Meta,
+        DropHandler,
+        ptr_into_parts,
+        ptr_from_parts,
+    },
+    array_vec::{
+        InlineBytes,
+        INLINE_ALIGN,
+    },
+};
+
+use core::{
+    ops::{
+        Deref,
+        DerefMut,
+    },
+    mem::ManuallyDrop,
+    marker::{
+        PhantomData,
+        Unsize,
+    },
+    ptr::{
+        self,
+        NonNull,
+    },
+    fmt::{
+        self,
+        Debug,
+        Formatter,
+    },
+};
+use alloc::alloc::{
+    alloc,
+    dealloc,
+    handle_alloc_error,
+    Layout,
+};
+
+/// Single value of an unsized type, stored inline if it fits.
+///
+/// The value is stored inline if it takes up to `CAP` bytes, and is aligned
+/// to at most 16 bytes. This makes it possible to keep, for example, a
+/// `dyn FnMut` in a struct field without allocating.
+///
+/// `try_new` fails if the value doesn't fit inline, whereas `new` moves it to
+/// the heap.
+pub struct HeteroSizedBox<T: ?Sized, const CAP: usize> {
+    // the value, if it is stored inline
+    inline: InlineBytes<CAP>,
+    // the value's heap allocation, if it is not stored inline
+    heap: Option<NonNull<u8>>,
+    // fat-pointer metadata of the value
+    ptr_meta: Meta<T>,
+    drop_handler: Option<DropHandler<T>>,
+
+    p: PhantomData<T>,
+}
+
+unsafe impl<T: ?Sized + Send, const CAP: usize> Send for HeteroSizedBox<T, CAP> {}
+
+unsafe impl<T: ?Sized + Sync, const CAP: usize> Sync for HeteroSizedBox<T, CAP> {}
+
+impl<T: ?Sized, const CAP: usize> HeteroSizedBox<T, CAP> {
+    /// Whether an element would be stored inline.
+    fn fits_inline<E: HeteroSizedPush<T>>(elem: &E) -> bool {
+        unsafe {
+            elem.elem_size() <= CAP && elem.elem_align() <= INLINE_ALIGN
+        }
+    }
+
+    // move the element into a box, given its heap allocation if it has one
+    unsafe fn new_at<E>(elem: E, heap: Option<NonNull<u8>>) -> Self
+    where
+        E: HeteroSizedPush<T>,
+    {
+        // prevent double-free in panic
+        let mut elem = ManuallyDrop::new(elem);
+
+        let (elem_data_ptr, meta) = ptr_into_parts::<T>(elem.elem_ptr());
+        let mut boxed: Self = HeteroSizedBox {
+            inline: InlineBytes::new(),
+            heap,
+            ptr_meta: meta,
+            drop_handler: elem.elem_drop_handler(),
+
+            p: PhantomData,
+        };
+
+        ptr::copy_nonoverlapping::<u8>(
+            // src:
+            elem_data_ptr,
+            // dst:
+            boxed.data_ptr(),
+            // len:
+            elem.elem_size(),
+        );
+
+        elem.outer_drop();
+
+        boxed
+    }
+
+    /// Try to box an element inline.
+    ///
+    /// Gives the element back if it doesn't fit.
+    pub fn try_new<E: HeteroSizedPush<T>>(elem: E) -> Result<Self, E> {
+        if !Self::fits_inline(&elem) {
+            return Err(elem);
+        }
+        unsafe {
+            Ok(Self::new_at(elem, None))
+        }
+    }
+
+    /// Try to box some value which unsizes to the element type inline.
+    ///
+    /// Gives the value back if it doesn't fit.
+    pub fn try_new_value<E: Unsize<T>>(elem: E) -> Result<Self, E> {
+        Self::try_new(InPlace(elem)).map_err(|InPlace(elem)| elem)
+    }
+
+    /// Box an element, inline if it fits, or on the heap if it doesn't.
+    pub fn new<E: HeteroSizedPush<T>>(elem: E) -> Self {
+        if Self::fits_inline(&elem) {
+            return unsafe { Self::new_at(elem, None) };
+        }
+        unsafe {
+            let layout = Layout::from_size_align(
+                elem.elem_size(),
+                elem.elem_align(),
+            ).unwrap();
+            let heap: NonNull<u8> = match layout.size() {
+                // zero-sized allocations are not allowed, so use a
+                // dangling, aligned pointer
+                0 => NonNull::new_unchecked(ptr::without_provenance_mut(layout.align())),
+                _ => match NonNull::new(alloc(layout)) {
+                    Some(heap) => heap,
+                    None => handle_alloc_error(layout),
+                },
+            };
+            Self::new_at(elem, Some(heap))
+        }
+    }
+
+    /// Box some value which unsizes to the element type, inline if it fits,
+    /// or on the heap if it doesn't.
+    pub fn new_value<E: Unsize<T>>(elem: E) -> Self {
+        Self::new(InPlace(elem))
+    }
+
+    /// Whether the value is stored inline.
+    pub fn is_inline(&self) -> bool {
+        self.heap.is_none()
+    }
+
+    fn data_ptr(&mut self) -> *mut u8 {
+        match self.heap {
+            Some(heap) => heap.as_ptr(),
+            None => self.inline.as_mut_ptr(),
+        }
+    }
+}
+
+impl<T: ?Sized, const CAP: usize> Deref for HeteroSizedBox<T, CAP> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        let data: *const u8 = match self.heap {
+            Some(heap) => heap.as_ptr(),
+            None => self.inline.as_ptr(),
+        };
+        unsafe {
+            &*ptr_from_parts::<T>(data as *mut u8, self.ptr_meta)
+        }
+    }
+}
+
+impl<T: ?Sized, const CAP: usize> DerefMut for HeteroSizedBox<T, CAP> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe {
+            &mut *ptr_from_parts::<T>(self.data_ptr(), self.ptr_meta)
+        }
+    }
+}
+
+impl<T: ?Sized, const CAP: usize> Drop for HeteroSizedBox<T, CAP> {
+    fn drop(&mut self) {
+        unsafe {
+            // the layout must be read before the value is dropped
+            let layout = Layout::for_value::<T>(&**self);
+            if let Some(destructor) = self.drop_handler {
+                destructor(self.data_ptr(), self.ptr_meta);
+            }
+            if let Some(heap) = self.heap {
+                if layout.size() > 0 {
+                    dealloc(heap.as_ptr(), layout);
+                }
+            }
+        }
+    }
+}
+
+// debug
+
+impl<T: ?Sized + Debug, const CAP: usize> Debug for HeteroSizedBox<T, CAP> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
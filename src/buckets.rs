@@ -0,0 +1,241 @@
+
+use crate::{
+    HeteroSizedVec,
+    pushable::{
+        HeteroSizedPush,
+        InPlace,
+    },
+    elem_type::ElemType,
+};
+
+use core::{
+    any::TypeId,
+    ops::{
+        Index,
+        IndexMut,
+    },
+    slice,
+    marker::Unsize,
+    ptr::{
+        slice_from_raw_parts,
+        slice_from_raw_parts_mut,
+    },
+};
+use alloc::{
+    vec::Vec,
+    collections::BTreeMap,
+};
+
+/// Collection of an unsized type, with one dense run of elements per
+/// concrete type.
+///
+/// This keeps track of insertion order, so it can be indexed and iterated
+/// like a `HeteroSizedVec`, but it also supports accessing all elements of
+/// a concrete type as a slice.
+pub struct HeteroSizedBuckets<T: ?Sized> {
+    // one vector per concrete type, each holding only elements of that type
+    buckets: Vec<HeteroSizedVec<T>>,
+    // the concrete type of each bucket
+    bucket_types: Vec<ElemType>,
+    // bucket index of each concrete type
+    bucket_indices: BTreeMap<TypeId, usize>,
+    // (bucket index, index within bucket) of each element,
+    // in insertion order
+    order: Vec<(usize, usize)>,
+}
+
+impl<T: ?Sized> HeteroSizedBuckets<T> {
+    /// New, empty collection.
+    pub fn new() -> Self {
+        HeteroSizedBuckets {
+            buckets: Vec::new(),
+            bucket_types: Vec::new(),
+            bucket_indices: BTreeMap::new(),
+            order: Vec::new(),
+        }
+    }
+
+    /// Length in elements.
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    /// Whether there are no elements.
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    /// Push an element into the bucket of its concrete type.
+    pub fn push<E: HeteroSizedPush<T>>(&mut self, elem: E) {
+        let elem_type: ElemType = unsafe { elem.elem_type() };
+
+        let buckets = &mut self.buckets;
+        let bucket_types = &mut self.bucket_types;
+        let bucket: usize = *self.bucket_indices
+            .entry(elem_type.id())
+            .or_insert_with(|| {
+                // the elements must be contiguous to be sliced, so there
+                // can't be guard bytes between them
+                let mut bucket = HeteroSizedVec::new();
+                bucket.guard_len = 0;
+                buckets.push(bucket);
+                bucket_types.push(elem_type);
+                buckets.len() - 1
+            });
+
+        self.buckets[bucket].push(elem);
+        self.order.push((bucket, self.buckets[bucket].len() - 1));
+    }
+
+    /// Push some value which unsizes to the element type.
+    pub fn push_value<E: Unsize<T>>(&mut self, elem: E) {
+        self.push(InPlace(elem));
+    }
+
+    /// Get element by insertion index as reference.
+    pub fn get(&self, index: usize) -> Option<&T> {
+        let &(bucket, i) = self.order.get(index)?;
+        self.buckets[bucket].get(i)
+    }
+
+    /// Get element by insertion index as mutable reference.
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        let &(bucket, i) = self.order.get(index)?;
+        self.buckets[bucket].get_mut(i)
+    }
+
+    /// The concrete types present, in order of first insertion.
+    pub fn types(&self) -> &[ElemType] {
+        &self.bucket_types
+    }
+
+    /// Iterate over the elements in insertion order.
+    pub fn iter(&self) -> BucketsIter<'_, T> {
+        BucketsIter {
+            buckets: &self.buckets,
+            order: self.order.iter(),
+        }
+    }
+
+    /// Iterate mutably over the elements in insertion order.
+    pub fn iter_mut(&mut self) -> BucketsIterMut<'_, T> {
+        BucketsIterMut {
+            buckets: &mut self.buckets,
+            order: self.order.iter(),
+        }
+    }
+}
+
+impl<T: ?Sized> Default for HeteroSizedBuckets<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: ?Sized + 'static> HeteroSizedBuckets<T> {
+    // the bucket for a concrete type, if any elements of it were pushed
+    fn bucket_of<C: 'static>(&self) -> Option<usize> {
+        self.bucket_indices.get(&TypeId::of::<C>()).copied()
+    }
+
+    /// All elements of concrete type `C`, in insertion order.
+    pub fn slice_of<C: 'static>(&self) -> &[C] {
+        let bucket = match self.bucket_of::<C>() {
+            Some(bucket) => &self.buckets[bucket],
+            None => return &[],
+        };
+        if bucket.is_empty() {
+            return &[];
+        }
+        unsafe {
+            &*slice_from_raw_parts(
+                bucket.elem_data_ptr(0) as *const C,
+                bucket.len(),
+            )
+        }
+    }
+
+    /// All elements of concrete type `C`, in insertion order, mutably.
+    pub fn slice_of_mut<C: 'static>(&mut self) -> &mut [C] {
+        let bucket = match self.bucket_of::<C>() {
+            Some(bucket) => &mut self.buckets[bucket],
+            None => return &mut [],
+        };
+        if bucket.is_empty() {
+            return &mut [];
+        }
+        unsafe {
+            &mut *slice_from_raw_parts_mut(
+                bucket.elem_data_ptr(0) as *mut C,
+                bucket.len(),
+            )
+        }
+    }
+}
+
+// index operator
+
+impl<T: ?Sized> Index<usize> for HeteroSizedBuckets<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        self.get(index).unwrap()
+    }
+}
+
+impl<T: ?Sized> IndexMut<usize> for HeteroSizedBuckets<T> {
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        self.get_mut(index).unwrap()
+    }
+}
+
+// iterators
+
+pub struct BucketsIter<'a, T: ?Sized> {
+    buckets: &'a [HeteroSizedVec<T>],
+    order: slice::Iter<'a, (usize, usize)>,
+}
+
+impl<'a, T: ?Sized> Iterator for BucketsIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.order.next()
+            .map(|&(bucket, i)| &self.buckets[bucket][i])
+    }
+}
+
+pub struct BucketsIterMut<'a, T: ?Sized> {
+    buckets: &'a mut [HeteroSizedVec<T>],
+    order: slice::Iter<'a, (usize, usize)>,
+}
+
+impl<'a, T: ?Sized> Iterator for BucketsIterMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.order.next()
+            .map(|&(bucket, i)| unsafe {
+                // each element appears in the order once
+                &mut *(&mut self.buckets[bucket][i] as *mut T)
+            })
+    }
+}
+
+impl<'a, T: ?Sized> IntoIterator for &'a HeteroSizedBuckets<T> {
+    type Item = &'a T;
+    type IntoIter = BucketsIter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, T: ?Sized> IntoIterator for &'a mut HeteroSizedBuckets<T> {
+    type Item = &'a mut T;
+    type IntoIter = BucketsIterMut<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
@@ -0,0 +1,215 @@
+
+use crate::{
+    HeteroSizedVecDeque,
+    pushable::{
+        HeteroSizedPush,
+        InPlace,
+    },
+};
+
+use std::{
+    sync::{
+        Arc,
+        Mutex,
+        Condvar,
+        PoisonError,
+        mpsc::{
+            SendError,
+            RecvError,
+            TryRecvError,
+        },
+    },
+    mem::swap,
+    marker::Unsize,
+    fmt::{
+        self,
+        Debug,
+        Formatter,
+    },
+};
+
+/// Create a channel of an unsized type, with any number of senders and one
+/// receiver.
+///
+/// Messages are moved into a shared ring buffer, rather than being boxed
+/// one at a time.
+pub fn hetero_channel<T: ?Sized>() -> (HeteroSender<T>, HeteroReceiver<T>) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            queue: HeteroSizedVecDeque::new(),
+            senders: 1,
+            receiver_alive: true,
+        }),
+        ready: Condvar::new(),
+    });
+    let sender = HeteroSender {
+        shared: shared.clone(),
+    };
+    let receiver = HeteroReceiver {
+        shared,
+        local: HeteroSizedVecDeque::new(),
+    };
+    (sender, receiver)
+}
+
+struct Shared<T: ?Sized> {
+    // a panic while this is locked, eg. in a message's `HeteroSizedPush`
+    // impl, leaves the state consistent, so poisoning is ignored
+    state: Mutex<State<T>>,
+    // notified when a message is sent, or the last sender is dropped
+    ready: Condvar,
+}
+
+struct State<T: ?Sized> {
+    queue: HeteroSizedVecDeque<T>,
+    senders: usize,
+    receiver_alive: bool,
+}
+
+/// Sending half of a `hetero_channel`.
+pub struct HeteroSender<T: ?Sized> {
+    shared: Arc<Shared<T>>,
+}
+
+/// Receiving half of a `hetero_channel`.
+pub struct HeteroReceiver<T: ?Sized> {
+    shared: Arc<Shared<T>>,
+    // messages taken from the shared queue all at once, so that they can be
+    // received without locking
+    local: HeteroSizedVecDeque<T>,
+}
+
+impl<T: ?Sized> HeteroSender<T> {
+    /// Send a message.
+    ///
+    /// Gives the message back if the receiver has been dropped.
+    pub fn send<E: HeteroSizedPush<T>>(&self, elem: E) -> Result<(), SendError<E>> {
+        let mut state = self.shared.state.lock().unwrap_or_else(PoisonError::into_inner);
+        if !state.receiver_alive {
+            return Err(SendError(elem));
+        }
+        state.queue.push_back(elem);
+        drop(state);
+
+        self.shared.ready.notify_one();
+        Ok(())
+    }
+
+    /// Send some value which unsizes to the message type.
+    ///
+    /// Gives the value back if the receiver has been dropped.
+    pub fn send_value<E: Unsize<T>>(&self, elem: E) -> Result<(), SendError<E>> {
+        self.send(InPlace(elem))
+            .map_err(|SendError(InPlace(elem))| SendError(elem))
+    }
+}
+
+impl<T: ?Sized> Clone for HeteroSender<T> {
+    fn clone(&self) -> Self {
+        self.shared.state.lock().unwrap_or_else(PoisonError::into_inner).senders += 1;
+        HeteroSender {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T: ?Sized> Drop for HeteroSender<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.senders -= 1;
+        let last: bool = state.senders == 0;
+        drop(state);
+
+        if last {
+            // wake up the receiver, to find that no more messages are coming
+            self.shared.ready.notify_all();
+        }
+    }
+}
+
+impl<T: ?Sized> HeteroReceiver<T> {
+    // take all messages from the shared queue into the local queue, waiting
+    // for there to be some if `block`
+    fn refill(&mut self, block: bool) -> Result<(), TryRecvError> {
+        if !self.local.is_empty() {
+            return Ok(());
+        }
+
+        let mut state = self.shared.state.lock().unwrap_or_else(PoisonError::into_inner);
+        while state.queue.is_empty() {
+            if state.senders == 0 {
+                return Err(TryRecvError::Disconnected);
+            }
+            if !block {
+                return Err(TryRecvError::Empty);
+            }
+            state = self.shared.ready.wait(state).unwrap_or_else(PoisonError::into_inner);
+        }
+        // this hands over the local queue's buffer for reuse
+        swap(&mut state.queue, &mut self.local);
+        Ok(())
+    }
+
+    /// Receive a message as a box, blocking until there is one.
+    ///
+    /// Fails once all senders have been dropped, and there are no more
+    /// messages.
+    pub fn recv(&mut self) -> Result<Box<T>, RecvError> {
+        self.refill(true).map_err(|_| RecvError)?;
+        Ok(self.local.pop_front().unwrap())
+    }
+
+    /// Receive a message as a box, if there is one.
+    pub fn try_recv(&mut self) -> Result<Box<T>, TryRecvError> {
+        self.refill(false)?;
+        Ok(self.local.pop_front().unwrap())
+    }
+
+    /// Receive a message, blocking until there is one, and pass it to `f`
+    /// before dropping it in place.
+    ///
+    /// This avoids moving the message into a box.
+    pub fn recv_with<R, F>(&mut self, f: F) -> Result<R, RecvError>
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        self.refill(true).map_err(|_| RecvError)?;
+        Ok(self.local.pop_front_with(f).unwrap())
+    }
+
+    /// Receive a message, if there is one, and pass it to `f` before dropping
+    /// it in place.
+    pub fn try_recv_with<R, F>(&mut self, f: F) -> Result<R, TryRecvError>
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        self.refill(false)?;
+        Ok(self.local.pop_front_with(f).unwrap())
+    }
+}
+
+impl<T: ?Sized> Drop for HeteroReceiver<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.receiver_alive = false;
+        // drop the remaining messages outside of the lock
+        let mut queue = HeteroSizedVecDeque::new();
+        swap(&mut state.queue, &mut queue);
+        drop(state);
+        drop(queue);
+    }
+}
+
+// debug
+
+impl<T: ?Sized> Debug for HeteroSender<T> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str("HeteroSender { .. }")
+    }
+}
+
+impl<T: ?Sized> Debug for HeteroReceiver<T> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str("HeteroReceiver { .. }")
+    }
+}
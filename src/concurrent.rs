@@ -0,0 +1,423 @@
+
+use crate::{
+    pushable::{
+        HeteroSizedPush,
+        InPlace,
+    },
+    memory::{
+        Meta,
+        DropHandler,
+        align_up,
+        ptr_into_parts,
+        ptr_from_parts,
+    },
+};
+
+use core::{
+    ops::{
+        Index,
+        Range,
+    },
+    mem::{
+        ManuallyDrop,
+        MaybeUninit,
+    },
+    marker::{
+        PhantomData,
+        Unsize,
+    },
+    cell::UnsafeCell,
+    sync::atomic::{
+        AtomicBool,
+        AtomicPtr,
+        AtomicUsize,
+        Ordering,
+    },
+    ptr::{
+        self,
+        NonNull,
+    },
+    cmp::max,
+    fmt::{
+        self,
+        Debug,
+        Formatter,
+    },
+};
+use alloc::{
+    alloc::{
+        alloc,
+        alloc_zeroed,
+        dealloc,
+        handle_alloc_error,
+        Layout,
+    },
+    boxed::Box,
+};
+
+/// Number of slots in the first segment of slots.
+///
+/// Each segment after that is twice as big as the one before it.
+const FIRST_SEGMENT_LEN: usize = 32;
+
+/// Maximum number of segments of slots.
+const SEGMENTS: usize = 32;
+
+/// Size in bytes of the first chunk of element storage.
+const FIRST_CHUNK_CAP: usize = 1024;
+
+/// Alignment of chunks of element storage.
+const CHUNK_ALIGN: usize = 16;
+
+/// Append-only vector of an unsized type, which can be pushed onto from
+/// many threads at once.
+///
+/// Elements are pushed through a shared reference, without locking. They are
+/// stored in chunks which are never reallocated, so an element never moves
+/// once pushed, and can be read by other threads while more elements are
+/// being pushed.
+///
+/// An element is visible to `get` and `iter` once its push has completed.
+pub struct ConcurrentHeteroSizedVec<T: ?Sized + Send + Sync> {
+    // segments of the slot table, which are allocated when first needed
+    segments: [AtomicPtr<Slot<T>>; SEGMENTS],
+    // number of indices given out to pushes
+    len: AtomicUsize,
+    // chunk currently being allocated from, which links to the previous ones
+    chunk: AtomicPtr<Chunk>,
+
+    p: PhantomData<T>,
+}
+
+// an index in the vector, which is written once by the push which reserved
+// it, then published to readers
+struct Slot<T: ?Sized> {
+    published: AtomicBool,
+    elem: UnsafeCell<MaybeUninit<SlotElem<T>>>,
+}
+
+struct SlotElem<T: ?Sized> {
+    data: *mut u8,
+    // fat-pointer metadata
+    ptr_meta: Meta<T>,
+    drop_handler: Option<DropHandler<T>>,
+}
+
+impl<T: ?Sized> Clone for SlotElem<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: ?Sized> Copy for SlotElem<T> {}
+
+// allocation which elements are bump-allocated from
+struct Chunk {
+    data: NonNull<u8>,
+    cap: usize,
+    // number of bytes handed out, which may exceed `cap` once it's full
+    used: AtomicUsize,
+    // the chunk which was allocated from before this one
+    prev: *mut Chunk,
+}
+
+unsafe impl<T: ?Sized + Send + Sync> Send for ConcurrentHeteroSizedVec<T> {}
+
+unsafe impl<T: ?Sized + Send + Sync> Sync for ConcurrentHeteroSizedVec<T> {}
+
+/// Segment containing an index, and the index within that segment.
+fn segment_of(index: usize) -> (usize, usize) {
+    let n: usize = index / FIRST_SEGMENT_LEN + 1;
+    let segment: usize = (usize::BITS - 1 - n.leading_zeros()) as usize;
+    (segment, index - FIRST_SEGMENT_LEN * ((1 << segment) - 1))
+}
+
+/// Layout of a segment of slots.
+fn segment_layout<T: ?Sized>(segment: usize) -> Layout {
+    Layout::array::<Slot<T>>(FIRST_SEGMENT_LEN << segment).unwrap()
+}
+
+impl<T: ?Sized + Send + Sync> ConcurrentHeteroSizedVec<T> {
+    /// New, empty vector.
+    pub fn new() -> Self {
+        ConcurrentHeteroSizedVec {
+            segments: Default::default(),
+            len: AtomicUsize::new(0),
+            chunk: AtomicPtr::new(ptr::null_mut()),
+
+            p: PhantomData,
+        }
+    }
+
+    /// Length in elements.
+    ///
+    /// This includes elements which are still being pushed by other
+    /// threads, and are not yet visible to `get`.
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Acquire)
+    }
+
+    /// Whether there are no elements, including ones still being pushed.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // get an index's slot, allocating its segment if necessary
+    fn slot_alloc(&self, index: usize) -> &Slot<T> {
+        let (segment, i) = segment_of(index);
+        assert!(segment < SEGMENTS, "ConcurrentHeteroSizedVec is full");
+
+        let mut slots: *mut Slot<T> = self.segments[segment].load(Ordering::Acquire);
+        if slots.is_null() {
+            unsafe {
+                let layout = segment_layout::<T>(segment);
+                // all-zeroes is a valid, unpublished slot
+                let new_slots = alloc_zeroed(layout) as *mut Slot<T>;
+                if new_slots.is_null() {
+                    handle_alloc_error(layout);
+                }
+                slots = match self.segments[segment].compare_exchange(
+                    ptr::null_mut(),
+                    new_slots,
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
+                ) {
+                    Ok(_) => new_slots,
+                    // another thread allocated it first
+                    Err(slots) => {
+                        dealloc(new_slots as *mut u8, layout);
+                        slots
+                    },
+                };
+            }
+        }
+        unsafe {
+            &*slots.add(i)
+        }
+    }
+
+    // get an index's slot, if its element has been published
+    fn slot_published(&self, index: usize) -> Option<&Slot<T>> {
+        let (segment, i) = segment_of(index);
+        if segment >= SEGMENTS {
+            return None;
+        }
+        let slots: *mut Slot<T> = self.segments[segment].load(Ordering::Acquire);
+        if slots.is_null() {
+            return None;
+        }
+        let slot: &Slot<T> = unsafe { &*slots.add(i) };
+        match slot.published.load(Ordering::Acquire) {
+            true => Some(slot),
+            false => None,
+        }
+    }
+
+    // bump-allocate bytes for an element
+    fn alloc_bytes(&self, size: usize, align: usize) -> *mut u8 {
+        if size == 0 {
+            // dangling, aligned pointer
+            return ptr::without_provenance_mut(align);
+        }
+        // with this much, the element fits at any alignment
+        let needed: usize = size + align - 1;
+        unsafe {
+            loop {
+                let chunk: *mut Chunk = self.chunk.load(Ordering::Acquire);
+                if !chunk.is_null() {
+                    let start: usize = (*chunk).used.fetch_add(needed, Ordering::Relaxed);
+                    if start + needed <= (*chunk).cap {
+                        let addr: usize = (*chunk).data.as_ptr().addr() + start;
+                        let padding: usize = align_up(addr, align) - addr;
+                        return (*chunk).data.as_ptr().add(start + padding);
+                    }
+                }
+
+                // the chunk is full, so replace it
+                let cap: usize = match chunk.is_null() {
+                    true => max(FIRST_CHUNK_CAP, needed),
+                    false => max((*chunk).cap * 2, needed),
+                };
+                let new_chunk: *mut Chunk = Chunk::alloc(cap, chunk);
+                if self.chunk.compare_exchange(
+                    chunk,
+                    new_chunk,
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
+                ).is_err() {
+                    // another thread replaced it first
+                    Chunk::free(new_chunk);
+                }
+            }
+        }
+    }
+
+    /// Push an element onto the vector, and get its index.
+    pub fn push<E: HeteroSizedPush<T>>(&self, elem: E) -> usize {
+        let index: usize = self.len.fetch_add(1, Ordering::AcqRel);
+        let slot: &Slot<T> = self.slot_alloc(index);
+        unsafe {
+            // prevent double-free in panic
+            let mut elem = ManuallyDrop::new(elem);
+
+            let elem_size: usize = elem.elem_size();
+            let data: *mut u8 = self.alloc_bytes(elem_size, elem.elem_align());
+
+            let (elem_data_ptr, meta) = ptr_into_parts::<T>(elem.elem_ptr());
+            ptr::copy_nonoverlapping::<u8>(
+                // src:
+                elem_data_ptr,
+                // dst:
+                data,
+                // len:
+                elem_size,
+            );
+            *slot.elem.get() = MaybeUninit::new(SlotElem {
+                data,
+                ptr_meta: meta,
+                drop_handler: elem.elem_drop_handler(),
+            });
+
+            elem.outer_drop();
+
+            slot.published.store(true, Ordering::Release);
+        }
+        index
+    }
+
+    /// Push some value which unsizes to the element type, and get its index.
+    pub fn push_value<E: Unsize<T>>(&self, elem: E) -> usize {
+        self.push(InPlace(elem))
+    }
+
+    /// Get element by index as reference.
+    ///
+    /// Returns `None` if out of bounds, or if the element is still being
+    /// pushed.
+    pub fn get(&self, index: usize) -> Option<&T> {
+        let slot: &Slot<T> = self.slot_published(index)?;
+        unsafe {
+            let elem: SlotElem<T> = (*slot.elem.get()).assume_init();
+            Some(&*ptr_from_parts::<T>(elem.data, elem.ptr_meta))
+        }
+    }
+
+    /// Iterate over the elements which have been published, in order.
+    pub fn iter(&self) -> ConcurrentIter<'_, T> {
+        ConcurrentIter {
+            vec: self,
+            index: 0..self.len(),
+        }
+    }
+}
+
+impl Chunk {
+    unsafe fn alloc(cap: usize, prev: *mut Chunk) -> *mut Chunk {
+        let layout = Layout::from_size_align(cap, CHUNK_ALIGN).unwrap();
+        let data: NonNull<u8> = match NonNull::new(alloc(layout)) {
+            Some(data) => data,
+            None => handle_alloc_error(layout),
+        };
+        Box::into_raw(Box::new(Chunk {
+            data,
+            cap,
+            used: AtomicUsize::new(0),
+            prev,
+        }))
+    }
+
+    unsafe fn free(chunk: *mut Chunk) {
+        let chunk: Box<Chunk> = Box::from_raw(chunk);
+        dealloc(
+            chunk.data.as_ptr(),
+            Layout::from_size_align_unchecked(chunk.cap, CHUNK_ALIGN),
+        );
+    }
+}
+
+impl<T: ?Sized + Send + Sync> Default for ConcurrentHeteroSizedVec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: ?Sized + Send + Sync> Drop for ConcurrentHeteroSizedVec<T> {
+    fn drop(&mut self) {
+        unsafe {
+            // drop elements
+            for index in 0..*self.len.get_mut() {
+                if let Some(slot) = self.slot_published(index) {
+                    let elem: SlotElem<T> = (*slot.elem.get()).assume_init();
+                    if let Some(destructor) = elem.drop_handler {
+                        destructor(elem.data, elem.ptr_meta);
+                    }
+                }
+            }
+
+            // free slots
+            for (segment, slots) in self.segments.iter_mut().enumerate() {
+                let slots: *mut Slot<T> = *slots.get_mut();
+                if !slots.is_null() {
+                    dealloc(slots as *mut u8, segment_layout::<T>(segment));
+                }
+            }
+
+            // free chunks
+            let mut chunk: *mut Chunk = *self.chunk.get_mut();
+            while !chunk.is_null() {
+                let prev: *mut Chunk = (*chunk).prev;
+                Chunk::free(chunk);
+                chunk = prev;
+            }
+        }
+    }
+}
+
+// index operator
+
+impl<T: ?Sized + Send + Sync> Index<usize> for ConcurrentHeteroSizedVec<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        self.get(index).unwrap()
+    }
+}
+
+// iterators
+
+pub struct ConcurrentIter<'a, T: ?Sized + Send + Sync> {
+    vec: &'a ConcurrentHeteroSizedVec<T>,
+    index: Range<usize>,
+}
+
+impl<'a, T: ?Sized + Send + Sync> Iterator for ConcurrentIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // skip elements which are still being pushed
+        let vec = self.vec;
+        self.index.find_map(|i| vec.get(i))
+    }
+}
+
+impl<'a, T: ?Sized + Send + Sync> IntoIterator for &'a ConcurrentHeteroSizedVec<T> {
+    type Item = &'a T;
+    type IntoIter = ConcurrentIter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+// debug
+
+impl<T: ?Sized + Send + Sync> Debug for ConcurrentHeteroSizedVec<T>
+where
+    for<'a> &'a T: Debug
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_list()
+            .entries(self)
+            .finish()
+    }
+}
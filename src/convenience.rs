@@ -4,7 +4,8 @@ use crate::{
     pushable::InPlace,
 };
 
-use std::{
+use core::{
+    alloc::Allocator,
     ops::{
         Index,
         IndexMut,
@@ -18,8 +19,9 @@ use std::{
         Formatter,
     },
 };
+use alloc::alloc::Global;
 
-impl<T: ?Sized> HeteroSizedVec<T> {
+impl<T: ?Sized, A: Allocator> HeteroSizedVec<T, A> {
     /// Push some value which unsizes to the element type.
     pub fn push_value<E: Unsize<T>>(&mut self, elem: E) {
         self.push(InPlace(elem));
@@ -28,7 +30,7 @@ impl<T: ?Sized> HeteroSizedVec<T> {
 
 // index operator
 
-impl<T: ?Sized> Index<usize> for HeteroSizedVec<T> {
+impl<T: ?Sized, A: Allocator> Index<usize> for HeteroSizedVec<T, A> {
     type Output = T;
 
     fn index(&self, index: usize) -> &T {
@@ -36,7 +38,7 @@ impl<T: ?Sized> Index<usize> for HeteroSizedVec<T> {
     }
 }
 
-impl<T: ?Sized> IndexMut<usize> for HeteroSizedVec<T> {
+impl<T: ?Sized, A: Allocator> IndexMut<usize> for HeteroSizedVec<T, A> {
     fn index_mut(&mut self, index: usize) -> &mut T {
         self.get_mut(index).unwrap()
     }
@@ -51,16 +53,16 @@ unsafe fn unwrap_unchecked<T>(option: Option<T>) -> T {
     }
 }
 
-unsafe fn change_lifetime_mut<'a, 'b, T: ?Sized>(r: &'a mut T) -> &'b mut T {
+unsafe fn change_lifetime_mut<'b, T: ?Sized>(r: &mut T) -> &'b mut T {
     &mut *(r as *mut T)
 }
 
-pub struct Iter<'a, T: ?Sized> {
-    vec: &'a HeteroSizedVec<T>,
+pub struct Iter<'a, T: ?Sized, A: Allocator = Global> {
+    vec: &'a HeteroSizedVec<T, A>,
     index: Range<usize>,
 }
 
-impl<'a, T: ?Sized> Iterator for Iter<'a, T> {
+impl<'a, T: ?Sized, A: Allocator> Iterator for Iter<'a, T, A> {
     type Item = &'a T;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -71,26 +73,26 @@ impl<'a, T: ?Sized> Iterator for Iter<'a, T> {
     }
 }
 
-pub struct IterMut<'a, T: ?Sized> {
-    vec: &'a mut HeteroSizedVec<T>,
+pub struct IterMut<'a, T: ?Sized, A: Allocator = Global> {
+    vec: &'a mut HeteroSizedVec<T, A>,
     index: Range<usize>,
 }
 
-impl<'a, T: ?Sized> Iterator for IterMut<'a, T> {
+impl<'a, T: ?Sized, A: Allocator> Iterator for IterMut<'a, T, A> {
     type Item = &'a mut T;
 
     fn next(&mut self) -> Option<Self::Item> {
         self.index.next()
             .map(|i| unsafe {
                 change_lifetime_mut(unwrap_unchecked(
-                    self.vec.get_mut(i)
+                    self.vec.elem_mut(i)
                 ))
             })
     }
 }
 
-impl<'a, T: ?Sized> Iter<'a, T> {
-    pub fn new(vec: &'a HeteroSizedVec<T>) -> Self {
+impl<'a, T: ?Sized, A: Allocator> Iter<'a, T, A> {
+    pub fn new(vec: &'a HeteroSizedVec<T, A>) -> Self {
         Iter {
             index: 0..vec.len(),
             vec,
@@ -98,8 +100,9 @@ impl<'a, T: ?Sized> Iter<'a, T> {
     }
 }
 
-impl<'a, T: ?Sized> IterMut<'a, T> {
-    pub fn new(vec: &'a mut HeteroSizedVec<T>) -> Self {
+impl<'a, T: ?Sized, A: Allocator> IterMut<'a, T, A> {
+    pub fn new(vec: &'a mut HeteroSizedVec<T, A>) -> Self {
+        vec.debug_check();
         IterMut {
             index: 0..vec.len(),
             vec,
@@ -108,28 +111,28 @@ impl<'a, T: ?Sized> IterMut<'a, T> {
 }
 
 
-impl<T: ?Sized> HeteroSizedVec<T> {
-    pub fn iter(&self) -> Iter<T> {
+impl<T: ?Sized, A: Allocator> HeteroSizedVec<T, A> {
+    pub fn iter(&self) -> Iter<'_, T, A> {
         Iter::new(self)
     }
 
-    pub fn iter_mut(&mut self) -> IterMut<T> {
+    pub fn iter_mut(&mut self) -> IterMut<'_, T, A> {
         IterMut::new(self)
     }
 }
 
-impl<'a, T: ?Sized> IntoIterator for &'a HeteroSizedVec<T> {
+impl<'a, T: ?Sized, A: Allocator> IntoIterator for &'a HeteroSizedVec<T, A> {
     type Item = &'a T;
-    type IntoIter = Iter<'a, T>;
+    type IntoIter = Iter<'a, T, A>;
 
     fn into_iter(self) -> Self::IntoIter {
         self.iter()
     }
 }
 
-impl<'a, T: ?Sized> IntoIterator for &'a mut HeteroSizedVec<T> {
+impl<'a, T: ?Sized, A: Allocator> IntoIterator for &'a mut HeteroSizedVec<T, A> {
     type Item = &'a mut T;
-    type IntoIter = IterMut<'a, T>;
+    type IntoIter = IterMut<'a, T, A>;
 
     fn into_iter(self) -> Self::IntoIter {
         self.iter_mut()
@@ -138,7 +141,7 @@ impl<'a, T: ?Sized> IntoIterator for &'a mut HeteroSizedVec<T> {
 
 // debug
 
-impl<T: ?Sized> Debug for HeteroSizedVec<T>
+impl<T: ?Sized, A: Allocator> Debug for HeteroSizedVec<T, A>
 where
     for<'a> &'a T: Debug
 {
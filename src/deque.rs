@@ -0,0 +1,550 @@
+
+use crate::{
+    pushable::{
+        HeteroSizedPush,
+        InPlace,
+    },
+    memory::{
+        Meta,
+        DropHandler,
+        align_up,
+        ptr_into_parts,
+        ptr_from_parts,
+        move_into_box_in,
+    },
+};
+
+use core::{
+    ops::{
+        Index,
+        IndexMut,
+        Range,
+    },
+    mem::{
+        size_of_val,
+        align_of_val,
+        ManuallyDrop,
+    },
+    marker::{
+        PhantomData,
+        Unsize,
+    },
+    ptr::{
+        self,
+        NonNull,
+    },
+    cmp::max,
+    fmt::{
+        self,
+        Debug,
+        Formatter,
+    },
+};
+use alloc::{
+    alloc::{
+        alloc,
+        dealloc,
+        handle_alloc_error,
+        Global,
+        Layout,
+    },
+    boxed::Box,
+    collections::VecDeque,
+};
+
+/// Capacity in bytes of the first allocation.
+const MIN_CAP: usize = 64;
+
+/// Round `n` down to a multiple of `align`.
+fn align_down(n: usize, align: usize) -> usize {
+    n - n % align
+}
+
+/// Double-ended queue of an unsized type.
+///
+/// The elements are stored in a ring buffer of bytes, so that they can be
+/// pushed and popped at both ends. An element never wraps around the end of
+/// the buffer; if it doesn't fit before the end, the remaining bytes are
+/// skipped as padding.
+pub struct HeteroSizedVecDeque<T: ?Sized> {
+    // ring buffer of elements
+    buf: NonNull<u8>,
+    // size of the buffer, either zero or a power of two
+    cap: usize,
+    // alignment of the buffer, which is at least that of any element
+    buf_align: usize,
+    // positions of the start and end of the occupied bytes, which are taken
+    // modulo `cap` to index into the buffer, and may wrap around
+    head: usize,
+    tail: usize,
+    elems: VecDeque<Record<T>>,
+
+    p: PhantomData<T>,
+}
+
+struct Record<T: ?Sized> {
+    // start-index of the element within the buffer
+    offset: usize,
+    // number of occupied bytes belonging to the element, including the
+    // padding which precedes it
+    span: usize,
+    // fat-pointer metadata
+    ptr_meta: Meta<T>,
+    drop_handler: Option<DropHandler<T>>,
+}
+
+impl<T: ?Sized> Clone for Record<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: ?Sized> Copy for Record<T> {}
+
+unsafe impl<T: ?Sized + Send> Send for HeteroSizedVecDeque<T> {}
+
+unsafe impl<T: ?Sized + Sync> Sync for HeteroSizedVecDeque<T> {}
+
+impl<T: ?Sized> HeteroSizedVecDeque<T> {
+    /// New, empty queue.
+    pub fn new() -> Self {
+        HeteroSizedVecDeque {
+            buf: NonNull::dangling(),
+            cap: 0,
+            buf_align: 1,
+            head: 0,
+            tail: 0,
+            elems: VecDeque::new(),
+
+            p: PhantomData,
+        }
+    }
+
+    /// Length in elements.
+    pub fn len(&self) -> usize {
+        self.elems.len()
+    }
+
+    /// Whether there are no elements.
+    pub fn is_empty(&self) -> bool {
+        self.elems.is_empty()
+    }
+
+    // index into the buffer of a position
+    fn wrap(&self, pos: usize) -> usize {
+        pos & (self.cap - 1)
+    }
+
+    // find where an element would be placed behind the back element, as its
+    // offset and span, if there is room
+    fn place_back(&self, size: usize, align: usize) -> Option<(usize, usize)> {
+        if self.cap == 0 || size > self.cap || align > self.buf_align {
+            return None;
+        }
+        let free: usize = self.cap - self.tail.wrapping_sub(self.head);
+        let tail: usize = self.wrap(self.tail);
+
+        let start: usize = align_up(tail, align);
+        let (offset, span) = match start + size <= self.cap {
+            true => (start, start - tail + size),
+            // skip to the start of the buffer
+            false => (0, self.cap - tail + size),
+        };
+        match span <= free {
+            true => Some((offset, span)),
+            false => None,
+        }
+    }
+
+    // find where an element would be placed in front of the front element,
+    // as its offset and span, if there is room
+    fn place_front(&self, size: usize, align: usize) -> Option<(usize, usize)> {
+        if self.cap == 0 || size > self.cap || align > self.buf_align {
+            return None;
+        }
+        let free: usize = self.cap - self.tail.wrapping_sub(self.head);
+        let head: usize = match self.wrap(self.head) {
+            0 => self.cap,
+            head => head,
+        };
+
+        let (offset, span) = match head >= size {
+            true => {
+                let start: usize = align_down(head - size, align);
+                (start, head - start)
+            },
+            // skip to the end of the buffer
+            false => {
+                let start: usize = align_down(self.cap - size, align);
+                (start, head + self.cap - start)
+            },
+        };
+        match span <= free {
+            true => Some((offset, span)),
+            false => None,
+        }
+    }
+
+    // place an element, reallocating until there is room for it
+    fn reserve<E, P>(&mut self, elem: &E, place: P) -> (usize, usize)
+    where
+        E: HeteroSizedPush<T>,
+        P: Fn(&Self, usize, usize) -> Option<(usize, usize)>,
+    {
+        unsafe {
+            let elem_size: usize = elem.elem_size();
+            let elem_align: usize = elem.elem_align();
+            loop {
+                if let Some(placed) = place(self, elem_size, elem_align) {
+                    return placed;
+                }
+                self.grow(elem_size, elem_align);
+            }
+        }
+    }
+
+    // reallocate the buffer, with room for the elements packed from its
+    // start, followed by an element of the given size and alignment
+    fn grow(&mut self, size: usize, align: usize) {
+        unsafe {
+            let new_align: usize = max(self.buf_align, align);
+
+            let mut packed_len: usize = 0;
+            for record in &self.elems {
+                let elem: &T = &*self.elem_ptr(record);
+                packed_len = align_up(packed_len, align_of_val(elem))
+                    + size_of_val(elem);
+            }
+            let new_cap: usize = max(
+                (align_up(packed_len, align) + size).next_power_of_two(),
+                max(self.cap * 2, MIN_CAP),
+            );
+
+            let layout = Layout::from_size_align(new_cap, new_align).unwrap();
+            let new_buf: NonNull<u8> = match NonNull::new(alloc(layout)) {
+                Some(new_buf) => new_buf,
+                None => handle_alloc_error(layout),
+            };
+
+            // move the elements into the new buffer
+            let mut new_len: usize = 0;
+            for i in 0..self.elems.len() {
+                let record: Record<T> = self.elems[i];
+                let elem: &T = &*self.elem_ptr(&record);
+                let elem_size: usize = size_of_val(elem);
+                let start: usize = align_up(new_len, align_of_val(elem));
+
+                ptr::copy_nonoverlapping::<u8>(
+                    // src:
+                    self.buf.as_ptr().add(record.offset),
+                    // dst:
+                    new_buf.as_ptr().add(start),
+                    // len:
+                    elem_size,
+                );
+
+                self.elems[i].offset = start;
+                self.elems[i].span = start - new_len + elem_size;
+                new_len = start + elem_size;
+            }
+
+            self.dealloc_buf();
+            self.buf = new_buf;
+            self.cap = new_cap;
+            self.buf_align = new_align;
+            self.head = 0;
+            self.tail = new_len;
+        }
+    }
+
+    fn dealloc_buf(&mut self) {
+        if self.cap > 0 {
+            unsafe {
+                dealloc(
+                    self.buf.as_ptr(),
+                    Layout::from_size_align_unchecked(self.cap, self.buf_align),
+                );
+            }
+        }
+    }
+
+    // move an element into the buffer at the given offset
+    unsafe fn write_elem<E>(&mut self, elem: E, offset: usize, span: usize) -> Record<T>
+    where
+        E: HeteroSizedPush<T>,
+    {
+        // prevent double-free in panic
+        let mut elem = ManuallyDrop::new(elem);
+
+        let (elem_data_ptr, meta) = ptr_into_parts::<T>(elem.elem_ptr());
+        ptr::copy_nonoverlapping::<u8>(
+            // src:
+            elem_data_ptr,
+            // dst:
+            self.buf.as_ptr().add(offset),
+            // len:
+            elem.elem_size(),
+        );
+        let record = Record {
+            offset,
+            span,
+            ptr_meta: meta,
+            drop_handler: elem.elem_drop_handler(),
+        };
+
+        elem.outer_drop();
+
+        record
+    }
+
+    /// Push an element onto the back of the queue.
+    pub fn push_back<E: HeteroSizedPush<T>>(&mut self, elem: E) {
+        let (offset, span) = self.reserve(&elem, Self::place_back);
+        unsafe {
+            let record: Record<T> = self.write_elem(elem, offset, span);
+            self.tail = self.tail.wrapping_add(span);
+            self.elems.push_back(record);
+        }
+    }
+
+    /// Push some value which unsizes to the element type onto the back of
+    /// the queue.
+    pub fn push_back_value<E: Unsize<T>>(&mut self, elem: E) {
+        self.push_back(InPlace(elem));
+    }
+
+    /// Push an element onto the front of the queue.
+    pub fn push_front<E: HeteroSizedPush<T>>(&mut self, elem: E) {
+        let (offset, span) = self.reserve(&elem, Self::place_front);
+        unsafe {
+            let record: Record<T> = self.write_elem(elem, offset, span);
+            self.head = self.head.wrapping_sub(span);
+            self.elems.push_front(record);
+        }
+    }
+
+    /// Push some value which unsizes to the element type onto the front of
+    /// the queue.
+    pub fn push_front_value<E: Unsize<T>>(&mut self, elem: E) {
+        self.push_front(InPlace(elem));
+    }
+
+    // once empty, start over from the start of the buffer
+    //
+    // this doesn't touch the buffer, so a popped element remains readable
+    // until the next push
+    fn reset_if_empty(&mut self) {
+        if self.elems.is_empty() {
+            self.head = 0;
+            self.tail = 0;
+        }
+    }
+
+    /// Remove the front element, and move it into a box.
+    pub fn pop_front(&mut self) -> Option<Box<T>> {
+        let record: Record<T> = self.elems.pop_front()?;
+        self.head = self.head.wrapping_add(record.span);
+        self.reset_if_empty();
+        unsafe {
+            Some(self.box_elem(&record))
+        }
+    }
+
+    /// Remove the back element, and move it into a box.
+    pub fn pop_back(&mut self) -> Option<Box<T>> {
+        let record: Record<T> = self.elems.pop_back()?;
+        self.tail = self.tail.wrapping_sub(record.span);
+        self.reset_if_empty();
+        unsafe {
+            Some(self.box_elem(&record))
+        }
+    }
+
+    /// Remove the front element, and pass it to `f` before dropping it in
+    /// place.
+    ///
+    /// This avoids moving the element into a box.
+    pub fn pop_front_with<R, F>(&mut self, f: F) -> Option<R>
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        let record: Record<T> = self.elems.pop_front()?;
+        self.head = self.head.wrapping_add(record.span);
+        self.reset_if_empty();
+        unsafe {
+            // if `f` panics, the element is leaked
+            let elem: *mut T = self.elem_ptr(&record);
+            let result: R = f(&mut *elem);
+            if let Some(destructor) = record.drop_handler {
+                destructor(self.buf.as_ptr().add(record.offset), record.ptr_meta);
+            }
+            Some(result)
+        }
+    }
+
+    fn elem_ptr(&self, record: &Record<T>) -> *mut T {
+        unsafe {
+            ptr_from_parts::<T>(
+                self.buf.as_ptr().add(record.offset),
+                record.ptr_meta,
+            )
+        }
+    }
+
+    unsafe fn box_elem(&self, record: &Record<T>) -> Box<T> {
+        move_into_box_in::<T, Global>(
+            self.buf.as_ptr().add(record.offset),
+            record.ptr_meta,
+            Global,
+        )
+    }
+
+    /// Get element by index, counting from the front, as reference.
+    pub fn get(&self, index: usize) -> Option<&T> {
+        let record: &Record<T> = self.elems.get(index)?;
+        unsafe {
+            Some(&*self.elem_ptr(record))
+        }
+    }
+
+    /// Get element by index, counting from the front, as mutable reference.
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        let record: &Record<T> = self.elems.get(index)?;
+        unsafe {
+            Some(&mut *self.elem_ptr(record))
+        }
+    }
+
+    /// Reference to the front element.
+    pub fn front(&self) -> Option<&T> {
+        self.get(0)
+    }
+
+    /// Mutable reference to the front element.
+    pub fn front_mut(&mut self) -> Option<&mut T> {
+        self.get_mut(0)
+    }
+
+    /// Reference to the back element.
+    pub fn back(&self) -> Option<&T> {
+        self.get(self.len().checked_sub(1)?)
+    }
+
+    /// Mutable reference to the back element.
+    pub fn back_mut(&mut self) -> Option<&mut T> {
+        self.get_mut(self.len().checked_sub(1)?)
+    }
+
+    pub fn iter(&self) -> DequeIter<'_, T> {
+        DequeIter {
+            deque: self,
+            index: 0..self.len(),
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> DequeIterMut<'_, T> {
+        DequeIterMut {
+            index: 0..self.len(),
+            deque: self,
+        }
+    }
+}
+
+impl<T: ?Sized> Default for HeteroSizedVecDeque<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: ?Sized> Drop for HeteroSizedVecDeque<T> {
+    fn drop(&mut self) {
+        unsafe {
+            for record in &self.elems {
+                if let Some(destructor) = record.drop_handler {
+                    destructor(self.buf.as_ptr().add(record.offset), record.ptr_meta);
+                }
+            }
+        }
+        self.dealloc_buf();
+    }
+}
+
+// index operator
+
+impl<T: ?Sized> Index<usize> for HeteroSizedVecDeque<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        self.get(index).unwrap()
+    }
+}
+
+impl<T: ?Sized> IndexMut<usize> for HeteroSizedVecDeque<T> {
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        self.get_mut(index).unwrap()
+    }
+}
+
+// iterators
+
+pub struct DequeIter<'a, T: ?Sized> {
+    deque: &'a HeteroSizedVecDeque<T>,
+    index: Range<usize>,
+}
+
+impl<'a, T: ?Sized> Iterator for DequeIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.index.next()
+            .map(|i| &self.deque[i])
+    }
+}
+
+pub struct DequeIterMut<'a, T: ?Sized> {
+    deque: &'a mut HeteroSizedVecDeque<T>,
+    index: Range<usize>,
+}
+
+impl<'a, T: ?Sized> Iterator for DequeIterMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.index.next()
+            .map(|i| unsafe {
+                // each index is visited once
+                &mut *(&mut self.deque[i] as *mut T)
+            })
+    }
+}
+
+impl<'a, T: ?Sized> IntoIterator for &'a HeteroSizedVecDeque<T> {
+    type Item = &'a T;
+    type IntoIter = DequeIter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, T: ?Sized> IntoIterator for &'a mut HeteroSizedVecDeque<T> {
+    type Item = &'a mut T;
+    type IntoIter = DequeIterMut<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+// debug
+
+impl<T: ?Sized> Debug for HeteroSizedVecDeque<T>
+where
+    for<'a> &'a T: Debug
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_list()
+            .entries(self)
+            .finish()
+    }
+}
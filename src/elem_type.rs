@@ -0,0 +1,188 @@
+
+use crate::HeteroSizedVec;
+
+use core::{
+    alloc::Allocator,
+    any::{
+        type_name,
+        TypeId,
+    },
+    marker::PhantomData,
+    mem::transmute,
+    hash::{
+        Hash,
+        Hasher,
+    },
+    fmt::{
+        self,
+        Debug,
+        Formatter,
+    },
+};
+use alloc::{
+    alloc::Global,
+    vec,
+    vec::Vec,
+};
+
+/// The concrete type of an element, recorded when it was pushed.
+///
+/// Types which differ only in their lifetimes are considered the same. This
+/// is only relied on for typed access to vectors of a `'static` element type,
+/// whose elements are then all `'static` too.
+#[derive(Copy, Clone)]
+pub struct ElemType {
+    id: TypeId,
+    name: &'static str,
+}
+
+// lets us get a `TypeId` for a type which is not `'static`, by erasing the
+// lifetime of the trait object
+trait NonStaticAny {
+    fn get_type_id(&self) -> TypeId where Self: 'static;
+}
+
+impl<E: ?Sized> NonStaticAny for PhantomData<E> {
+    fn get_type_id(&self) -> TypeId where Self: 'static {
+        TypeId::of::<E>()
+    }
+}
+
+impl ElemType {
+    /// The `ElemType` of `E`.
+    ///
+    /// This is not public, as the same `TypeId` is given to types which
+    /// differ only in their lifetimes.
+    pub(crate) fn of<E: ?Sized>() -> Self {
+        let phantom: &dyn NonStaticAny = &PhantomData::<E>;
+        let phantom: &(dyn NonStaticAny + 'static) = unsafe {
+            transmute(phantom)
+        };
+        ElemType {
+            id: phantom.get_type_id(),
+            name: type_name::<E>(),
+        }
+    }
+
+    /// The `TypeId` of the type.
+    pub fn id(&self) -> TypeId {
+        self.id
+    }
+
+    /// The name of the type, as given by `std::any::type_name`.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl PartialEq for ElemType {
+    fn eq(&self, rhs: &Self) -> bool {
+        self.id == rhs.id
+    }
+}
+
+impl Eq for ElemType {}
+
+impl Hash for ElemType {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl Debug for ElemType {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(self.name)
+    }
+}
+
+impl<T: ?Sized> HeteroSizedVec<T> {
+    /// New, empty vector which records the concrete type of each element.
+    pub fn with_type_tracking() -> Self {
+        Self::with_type_tracking_in(Global)
+    }
+}
+
+impl<T: ?Sized, A: Allocator + Clone> HeteroSizedVec<T, A> {
+    /// New, empty vector which records the concrete type of each element,
+    /// and allocates with the given allocator.
+    pub fn with_type_tracking_in(alloc: A) -> Self {
+        let mut vec = Self::new_in(alloc.clone());
+        vec.elem_types = Some(Vec::new_in(alloc));
+        vec
+    }
+}
+
+impl<T: ?Sized, A: Allocator> HeteroSizedVec<T, A> {
+
+    /// Whether this vector records the concrete type of each element.
+    pub fn tracks_types(&self) -> bool {
+        self.elem_types.is_some()
+    }
+
+    /// Get the concrete type of an element by index.
+    ///
+    /// Returns `None` if out of bounds, or if types are not being tracked.
+    pub fn elem_type_of(&self, index: usize) -> Option<ElemType> {
+        self.elem_types.as_ref()?.get(index).copied()
+    }
+
+    /// Get the `TypeId` of an element's concrete type by index.
+    ///
+    /// Returns `None` if out of bounds, or if types are not being tracked.
+    pub fn type_id_of(&self, index: usize) -> Option<TypeId> {
+        self.elem_type_of(index).map(|ty| ty.id())
+    }
+
+    /// Get the name of an element's concrete type by index.
+    ///
+    /// Returns `None` if out of bounds, or if types are not being tracked.
+    pub fn type_name_of(&self, index: usize) -> Option<&'static str> {
+        self.elem_type_of(index).map(|ty| ty.name())
+    }
+
+    /// Group element indices by their concrete types, in order of each
+    /// type's first appearance.
+    ///
+    /// Returns `None` if types are not being tracked.
+    pub fn group_by_type(&self) -> Option<Vec<(ElemType, Vec<usize>)>> {
+        let mut groups: Vec<(ElemType, Vec<usize>)> = Vec::new();
+        for (i, &ty) in self.elem_types.as_ref()?.iter().enumerate() {
+            match groups.iter_mut().find(|&&mut (group, _)| group == ty) {
+                Some((_, indices)) => indices.push(i),
+                None => groups.push((ty, vec![i])),
+            }
+        }
+        Some(groups)
+    }
+
+    /// Debug-format the concrete type names of the elements, rather than
+    /// the elements themselves.
+    ///
+    /// Elements whose types are not being tracked are shown as `?`.
+    pub fn debug_types(&self) -> DebugTypes<'_, T, A> {
+        DebugTypes { vec: self }
+    }
+}
+
+/// Debug-formats the element types of a `HeteroSizedVec`.
+pub struct DebugTypes<'a, T: ?Sized, A: Allocator = Global> {
+    vec: &'a HeteroSizedVec<T, A>,
+}
+
+impl<'a, T: ?Sized, A: Allocator> Debug for DebugTypes<'a, T, A> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        struct Name(&'static str);
+
+        impl Debug for Name {
+            fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+                f.write_str(self.0)
+            }
+        }
+
+        f.debug_list()
+            .entries((0..self.vec.len()).map(|i| Name(
+                self.vec.type_name_of(i).unwrap_or("?")
+            )))
+            .finish()
+    }
+}
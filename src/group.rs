@@ -0,0 +1,80 @@
+
+use crate::HeteroSizedVec;
+
+use core::{
+    alloc::Allocator,
+    any::TypeId,
+    mem::size_of,
+    ptr::slice_from_raw_parts_mut,
+};
+
+impl<T: ?Sized + 'static, A: Allocator> HeteroSizedVec<T, A> {
+    /// Call `f` on each element of concrete type `C`, in order.
+    ///
+    /// This dispatches statically, rather than through `T`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if types are not being tracked, as with `with_type_tracking`.
+    pub fn for_each_of<C, F>(&mut self, mut f: F)
+    where
+        C: 'static,
+        F: FnMut(&mut C),
+    {
+        self.for_each_run_of::<C, _>(|run| {
+            for elem in run {
+                f(elem);
+            }
+        });
+    }
+
+    /// Call `f` on each run of adjacent elements of concrete type `C`, as a
+    /// slice, in order.
+    ///
+    /// Elements of the same type which were pushed consecutively are packed
+    /// like an array, so they can be visited as one run. With the
+    /// `debug-checks` feature, guard bytes separate every element, so each
+    /// run is a single element.
+    ///
+    /// # Panics
+    ///
+    /// Panics if types are not being tracked, as with `with_type_tracking`.
+    pub fn for_each_run_of<C, F>(&mut self, mut f: F)
+    where
+        C: 'static,
+        F: FnMut(&mut [C]),
+    {
+        let elem_types = self.elem_types.as_ref()
+            .expect("type tracking is not enabled");
+        let id: TypeId = TypeId::of::<C>();
+        let is_c = |i: usize| elem_types[i].id() == id;
+
+        let mut i: usize = 0;
+        while i < elem_types.len() {
+            if !is_c(i) {
+                i += 1;
+                continue;
+            }
+
+            // extend the run while the elements are contiguous
+            let start: usize = i;
+            i += 1;
+            while
+                i < elem_types.len()
+                && is_c(i)
+                && self.mem_indices[i]
+                    == self.mem_indices[i - 1] + size_of::<C>()
+            {
+                i += 1;
+            }
+
+            let run: &mut [C] = unsafe {
+                &mut *slice_from_raw_parts_mut(
+                    self.elem_data_ptr(start) as *mut C,
+                    i - start,
+                )
+            };
+            f(run);
+        }
+    }
+}
@@ -0,0 +1,122 @@
+
+use crate::HeteroSizedVec;
+
+use core::alloc::Allocator;
+use alloc::vec::Vec;
+
+/// Least number of guard bytes before each element and after the last one,
+/// which is only non-zero with the `debug-checks` feature.
+///
+/// This makes sure that an out of bounds write past the end of an element
+/// lands in padding, rather than in the next element or outside of storage.
+pub(crate) const GUARD_LEN: usize =
+    if cfg!(feature = "debug-checks") { 8 } else { 0 };
+
+/// Value of each byte of padding between elements.
+pub(crate) const PADDING_BYTE: u8 =
+    if cfg!(feature = "debug-checks") { 0xa5 } else { 0 };
+
+impl<T: ?Sized, A: Allocator> HeteroSizedVec<T, A> {
+    /// Check that the vector's bookkeeping is consistent, and that nothing
+    /// has written into the padding between elements.
+    ///
+    /// This does nothing unless the `debug-checks` feature is enabled, in
+    /// which case it's also done by `get_mut`, by `drop`, and before every
+    /// change to the layout of the vector. A write through a mutable
+    /// reference is thus caught by the next such call.
+    ///
+    /// Panics, naming the element written past, if a check fails.
+    #[inline]
+    pub fn debug_check(&self) {
+        if cfg!(feature = "debug-checks") {
+            self.check_integrity();
+        }
+    }
+
+    fn check_integrity(&self) {
+        let len: usize = self.mem_indices.len();
+        for &(name, other_len) in &[
+            ("elems_size", self.elems_size.len()),
+            ("drop_handlers", self.drop_handlers.len()),
+            ("ptr_meta", self.ptr_meta.len()),
+            ("elem_types", self.elem_types.as_ref().map_or(len, Vec::len)),
+        ] {
+            assert_eq!(
+                other_len, len,
+                "HeteroSizedVec has {} mem_indices, but {} {}",
+                len, other_len, name,
+            );
+        }
+
+        for index in 0..len {
+            assert!(
+                self.mem_indices[index] + self.elems_size[index]
+                    <= self.storage.len(),
+                "HeteroSizedVec element {} lies outside of storage",
+                index,
+            );
+        }
+
+        for (start, end, after) in self.padding_ranges() {
+            let overwritten = self.storage[start..end].iter()
+                .position(|&byte| byte != PADDING_BYTE);
+            if let Some(i) = overwritten {
+                match after {
+                    Some(index) => panic!(
+                        "HeteroSizedVec guard byte at offset {} was \
+                         overwritten, past the end of element {}",
+                        start + i, index,
+                    ),
+                    None => panic!(
+                        "HeteroSizedVec guard byte at offset {} was \
+                         overwritten",
+                        start + i,
+                    ),
+                }
+            }
+        }
+    }
+
+    // the ranges of storage which are neither occupied by an element nor
+    // freed, with the index of the element right before each, if any
+    fn padding_ranges(&self) -> Vec<(usize, usize, Option<usize>)> {
+        let mut occupied: Vec<(usize, usize, Option<usize>)> = self.mem_indices
+            .iter()
+            .zip(&self.elems_size)
+            .enumerate()
+            // zero-sized elements take no storage
+            .filter(|&(_, (_, &size))| size > 0)
+            .map(|(index, (&offset, &size))| (offset, offset + size, Some(index)))
+            .chain(self.free_ranges.iter().map(|&(start, end)| (start, end, None)))
+            .collect();
+        occupied.sort_by_key(|&(start, end, _)| (start, end));
+
+        let mut padding: Vec<(usize, usize, Option<usize>)> = Vec::new();
+        let mut pos: usize = 0;
+        let mut after: Option<usize> = None;
+        for (start, end, index) in occupied {
+            if start > pos {
+                padding.push((pos, start, after));
+            }
+            if end >= pos {
+                pos = end;
+                after = index;
+            }
+        }
+        if self.storage.len() > pos {
+            padding.push((pos, self.storage.len(), after));
+        }
+        padding
+    }
+
+    /// Overwrite all padding with `PADDING_BYTE`, for storage which was not
+    /// laid out by this vector.
+    #[cfg(feature = "std")]
+    pub(crate) fn fill_padding(&mut self) {
+        for (start, end, _) in self.padding_ranges() {
+            for byte in &mut self.storage[start..end] {
+                *byte = PADDING_BYTE;
+            }
+        }
+    }
+}
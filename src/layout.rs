@@ -0,0 +1,182 @@
+
+use crate::{
+    HeteroSizedVec,
+    memory::Meta,
+};
+
+use core::{
+    alloc::Allocator,
+    mem::{
+        size_of,
+        align_of_val,
+        transmute_copy,
+    },
+    fmt::{
+        self,
+        Display,
+        Formatter,
+    },
+};
+use alloc::{
+    vec,
+    vec::Vec,
+};
+
+/// Where an element lives in storage, as given by `layout_report`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ElemLayout {
+    /// Index of the element.
+    pub index: usize,
+    /// Offset in bytes of the element within storage.
+    pub offset: usize,
+    /// Size in bytes of the element.
+    pub size: usize,
+    /// Alignment of the element.
+    pub align: usize,
+    /// Bytes wasted on alignment between the element and whatever comes
+    /// before it in storage.
+    pub padding: usize,
+    /// Fat-pointer metadata, as a slice length or a vtable address, which
+    /// is zero if the pointer isn't fat.
+    pub ptr_meta: usize,
+}
+
+/// How much of one of the vector's buffers is used.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BufferUsage {
+    /// Name of the buffer.
+    pub name: &'static str,
+    /// Bytes in use.
+    pub used: usize,
+    /// Bytes allocated but not in use.
+    pub spare: usize,
+}
+
+/// Snapshot of how a vector's memory is used, as given by `layout_report`.
+///
+/// The `Display` impl prints it as a table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayoutReport {
+    /// Each element, in index order.
+    pub elems: Vec<ElemLayout>,
+    /// Bytes of storage occupied by elements.
+    pub live_bytes: usize,
+    /// Bytes of storage wasted on alignment.
+    pub padding_bytes: usize,
+    /// Bytes of storage freed by removed elements, which may be reused.
+    pub free_bytes: usize,
+    /// Bytes used by the per-element metadata, and the freed ranges.
+    pub metadata_bytes: usize,
+    /// The storage, then each metadata buffer.
+    pub buffers: Vec<BufferUsage>,
+}
+
+// usage of a `Vec` in bytes
+fn usage<I, A: Allocator>(name: &'static str, vec: &Vec<I, A>) -> BufferUsage {
+    BufferUsage {
+        name,
+        used: vec.len() * size_of::<I>(),
+        spare: (vec.capacity() - vec.len()) * size_of::<I>(),
+    }
+}
+
+// fat-pointer metadata as a word, which is only for display
+fn meta_word<T: ?Sized>(meta: Meta<T>) -> usize {
+    match size_of::<Meta<T>>() == size_of::<usize>() {
+        true => unsafe { transmute_copy::<Meta<T>, usize>(&meta) },
+        false => 0,
+    }
+}
+
+impl<T: ?Sized, A: Allocator> HeteroSizedVec<T, A> {
+    /// Report where each element lives in storage, and how much memory is
+    /// used and wasted.
+    pub fn layout_report(&self) -> LayoutReport {
+        let mut elems: Vec<ElemLayout> = self.iter()
+            .enumerate()
+            .map(|(index, elem)| ElemLayout {
+                index,
+                offset: self.mem_indices[index],
+                size: self.elems_size[index],
+                align: align_of_val(elem),
+                padding: 0,
+                ptr_meta: meta_word::<T>(self.ptr_meta[index]),
+            })
+            .collect();
+
+        // walk storage in order of offset, where any gap between one thing
+        // and the next that isn't a freed range is padding
+        let mut by_offset: Vec<usize> = (0..elems.len()).collect();
+        by_offset.sort_by_key(|&index| (elems[index].offset, elems[index].size));
+        let mut free_ranges = self.free_ranges.iter().copied().peekable();
+        let mut prev_end: usize = 0;
+        for index in by_offset {
+            let offset: usize = elems[index].offset;
+            while let Some(&(start, end)) = free_ranges.peek() {
+                if start >= offset {
+                    break;
+                }
+                prev_end = prev_end.max(end);
+                free_ranges.next();
+            }
+            elems[index].padding = offset.saturating_sub(prev_end);
+            prev_end = prev_end.max(offset + elems[index].size);
+        }
+
+        let mut buffers: Vec<BufferUsage> = vec![
+            BufferUsage {
+                name: "storage",
+                used: self.storage.len(),
+                spare: self.storage.capacity() - self.storage.len(),
+            },
+            usage("ptr_meta", &self.ptr_meta),
+            usage("mem_indices", &self.mem_indices),
+            usage("drop_handlers", &self.drop_handlers),
+            usage("elems_size", &self.elems_size),
+        ];
+        if let Some(ref elem_types) = self.elem_types {
+            buffers.push(usage("elem_types", elem_types));
+        }
+        buffers.push(usage("free_ranges", &self.free_ranges));
+
+        LayoutReport {
+            live_bytes: elems.iter().map(|elem| elem.size).sum(),
+            padding_bytes: elems.iter().map(|elem| elem.padding).sum(),
+            free_bytes: self.free_ranges.iter().map(|&(start, end)| end - start).sum(),
+            metadata_bytes: buffers[1..].iter().map(|buffer| buffer.used).sum(),
+            elems,
+            buffers,
+        }
+    }
+}
+
+impl Display for LayoutReport {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>8} {:>8} {:>8} {:>8} {:>8} {:>12}",
+            "index", "offset", "size", "align", "padding", "ptr_meta",
+        )?;
+        for elem in &self.elems {
+            writeln!(
+                f,
+                "{:>8} {:>8} {:>8} {:>8} {:>8} {:>12}",
+                elem.index,
+                elem.offset,
+                elem.size,
+                elem.align,
+                elem.padding,
+                elem.ptr_meta,
+            )?;
+        }
+        writeln!(f, "live bytes:     {}", self.live_bytes)?;
+        writeln!(f, "padding bytes:  {}", self.padding_bytes)?;
+        writeln!(f, "free bytes:     {}", self.free_bytes)?;
+        writeln!(f, "metadata bytes: {}", self.metadata_bytes)?;
+        writeln!(f, "spare capacity:")?;
+        for buffer in &self.buffers {
+            writeln!(f, "    {:<14} {}", buffer.name, buffer.spare)?;
+        }
+        Ok(())
+    }
+}
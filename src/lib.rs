@@ -1,15 +1,39 @@
-#![feature(external_doc)]
 #![feature(unsize)]
 #![feature(arbitrary_self_types)]
-#![feature(coerce_unsized)]
-#![feature(new_uninit)]
-#![feature(slice_from_raw_parts)]
+#![feature(allocator_api)]
+#![feature(ptr_metadata)]
+#![feature(layout_for_ptr)]
 
 #![allow(unused_parens)]
 
-#![doc(include = "../README.md")]
+#![cfg_attr(not(feature = "std"), no_std)]
 
-use std::marker::PhantomData;
+#![doc = include_str!("../README.md")]
+
+extern crate alloc;
+
+// the test suite uses `std` either way
+#[cfg(all(test, not(feature = "std")))]
+#[macro_use]
+extern crate std;
+
+use core::{
+    marker::PhantomData,
+    alloc::Allocator,
+};
+use alloc::{
+    alloc::Global,
+    vec::Vec,
+};
+
+use self::{
+    integrity::GUARD_LEN,
+    memory::{
+        Meta,
+        DropHandler,
+    },
+    storage::Storage,
+};
 
 /// `HeteroSizedPush` and its implementations.
 mod pushable;
@@ -17,9 +41,61 @@ mod pushable;
 /// Very unsafe memory management.
 mod memory;
 
+/// Aligned byte buffer for element storage.
+mod storage;
+
 /// Ease of use functions and implementations.
 mod convenience;
 
+/// In-place growing and shrinking of the last element.
+mod resize;
+
+/// Typed downcasting for `dyn Any` elements.
+mod any;
+
+/// Recording the concrete types of elements.
+mod elem_type;
+
+/// Iteration grouped by concrete type.
+mod group;
+
+/// Collection with a dense run per concrete type.
+mod buckets;
+
+/// Collection with stable handles.
+mod slot_map;
+
+/// Fixed-capacity collection with inline storage.
+mod array_vec;
+
+/// Collection with inline storage which can spill onto the heap.
+mod small_vec;
+
+/// Single value with inline storage.
+mod boxed;
+
+/// Double-ended queue with ring buffer storage.
+mod deque;
+
+/// Append-only collection which can be pushed onto concurrently.
+mod concurrent;
+
+/// Checking of the vector's integrity, with the `debug-checks` feature.
+mod integrity;
+
+/// Reporting of how storage is laid out.
+mod layout;
+
+/// Plain-data element types.
+mod pod;
+
+/// Binary serialization of `str` and slice elements.
+mod serial;
+
+/// Channel of unsized messages.
+#[cfg(feature = "std")]
+mod channel;
+
 #[cfg(test)]
 pub mod tests;
 
@@ -30,6 +106,49 @@ pub use self::pushable::{
     HeteroSizedPush,
     InPlace,
 };
+#[doc(inline)]
+pub use self::buckets::HeteroSizedBuckets;
+#[doc(inline)]
+pub use self::array_vec::HeteroSizedArrayVec;
+#[doc(inline)]
+pub use self::small_vec::HeteroSizedSmallVec;
+#[doc(inline)]
+pub use self::boxed::HeteroSizedBox;
+#[doc(inline)]
+pub use self::deque::HeteroSizedVecDeque;
+#[doc(inline)]
+pub use self::concurrent::ConcurrentHeteroSizedVec;
+#[doc(inline)]
+pub use self::layout::{
+    LayoutReport,
+    ElemLayout,
+    BufferUsage,
+};
+#[doc(inline)]
+pub use self::pod::HeteroPod;
+#[doc(inline)]
+pub use self::serial::{
+    HeteroSizedVecRef,
+    DecodeError,
+    FORMAT_VERSION,
+};
+#[cfg(feature = "std")]
+#[doc(inline)]
+pub use self::channel::{
+    hetero_channel,
+    HeteroSender,
+    HeteroReceiver,
+};
+#[doc(inline)]
+pub use self::slot_map::{
+    HeteroSizedSlotMap,
+    Handle,
+};
+#[doc(inline)]
+pub use self::elem_type::{
+    ElemType,
+    DebugTypes,
+};
 /// Iterators.
 pub mod iter {
     #[doc(inline)]
@@ -37,6 +156,32 @@ pub mod iter {
         Iter,
         IterMut,
     };
+    #[doc(inline)]
+    pub use crate::any::IterOf;
+    #[doc(inline)]
+    pub use crate::array_vec::{
+        ArrayVecIter,
+        ArrayVecIterMut,
+    };
+    #[doc(inline)]
+    pub use crate::small_vec::{
+        SmallVecIter,
+        SmallVecIterMut,
+    };
+    #[doc(inline)]
+    pub use crate::deque::{
+        DequeIter,
+        DequeIterMut,
+    };
+    #[doc(inline)]
+    pub use crate::concurrent::ConcurrentIter;
+    #[doc(inline)]
+    pub use crate::serial::RefIter;
+    #[doc(inline)]
+    pub use crate::buckets::{
+        BucketsIter,
+        BucketsIterMut,
+    };
 }
 
 /// Dense vector of an unsized type.
@@ -45,26 +190,41 @@ pub mod iter {
 ///
 /// - Pushing an element
 /// - Indexing
+/// - Removing an element, leaving space for later pushes to reuse
 /// - Conversion into a `Vec` of boxes
 ///
 /// This supports elements such as trait objects, `str`, and `[T]`.
-pub struct HeteroSizedVec<T: ?Sized> {
+pub struct HeteroSizedVec<T: ?Sized, A: Allocator = Global> {
     // densely packed elements
     // respects alignment rules
-    storage: Vec<u8>,
+    storage: Storage<A>,
     // fat-pointer metadata for each element
-    ptr_meta: Vec<usize>,
+    // this is `()` if the pointers are not fat, so takes no space
+    ptr_meta: Vec<Meta<T>, A>,
     // start-indices of each element within storage
-    mem_indices: Vec<usize>,
+    mem_indices: Vec<usize, A>,
     // handlers for dropping each element
     //
     // the given pointer is to the start address of the element
     // and the second element is fat pointer metadata
-    // unless the pointers are not fat, in which case it will be zero
-    drop_handlers: Vec<fn(*mut u8, usize)>,
+    //
+    // this is `None` for elements which have no destructor
+    drop_handlers: Vec<Option<DropHandler<T>>, A>,
+    // whether any element may have a drop handler
+    // if not, dropping the elements is skipped entirely
+    needs_drop: bool,
     // the runtime size of each element
     // this is used for moving them to the heap
-    elems_size: Vec<usize>,
+    elems_size: Vec<usize, A>,
+    // the concrete type of each element
+    // this is only recorded if type tracking is enabled
+    elem_types: Option<Vec<ElemType, A>>,
+    // (start, end) of each range of storage freed by removing elements
+    // these are sorted, and never adjacent to each other
+    free_ranges: Vec<(usize, usize), A>,
+    // least number of guard bytes before each element and after the last
+    // this is zero unless checking, or if the elements must be contiguous
+    guard_len: usize,
 
     p: PhantomData<T>,
 }
@@ -72,20 +232,48 @@ pub struct HeteroSizedVec<T: ?Sized> {
 impl<T: ?Sized> HeteroSizedVec<T> {
     /// New, empty vector.
     pub fn new() -> Self {
+        Self::new_in(Global)
+    }
+}
+
+impl<T: ?Sized> Default for HeteroSizedVec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: ?Sized, A: Allocator + Clone> HeteroSizedVec<T, A> {
+    /// New, empty vector, which allocates with the given allocator.
+    pub fn new_in(alloc: A) -> Self {
         HeteroSizedVec {
-            storage: Vec::new(),
-            ptr_meta: Vec::new(),
-            mem_indices: Vec::new(),
-            drop_handlers: Vec::new(),
-            elems_size: Vec::new(),
+            storage: Storage::new_in(alloc.clone()),
+            ptr_meta: Vec::new_in(alloc.clone()),
+            mem_indices: Vec::new_in(alloc.clone()),
+            drop_handlers: Vec::new_in(alloc.clone()),
+            elems_size: Vec::new_in(alloc.clone()),
+            elem_types: None,
+            free_ranges: Vec::new_in(alloc),
+            needs_drop: false,
+            guard_len: GUARD_LEN,
 
             p: PhantomData,
         }
     }
+}
+
+impl<T: ?Sized, A: Allocator> HeteroSizedVec<T, A> {
+    /// The allocator used by the vector.
+    pub fn allocator(&self) -> &A {
+        self.storage.allocator()
+    }
 
     /// Length in elements.
     pub fn len(&self) -> usize {
         self.mem_indices.len()
     }
-}
 
+    /// Whether there are no elements.
+    pub fn is_empty(&self) -> bool {
+        self.mem_indices.is_empty()
+    }
+}
@@ -2,71 +2,95 @@
 use crate::{
     HeteroSizedVec,
     pushable::HeteroSizedPush,
+    integrity::PADDING_BYTE,
+    storage::Storage,
 };
 
-use std::{
+use core::{
+    alloc::{
+        Allocator,
+        AllocError,
+        Layout,
+    },
     mem::{
-        size_of,
-        transmute_copy,
+        align_of_val,
+        align_of_val_raw,
         ManuallyDrop,
-        MaybeUninit,
     },
-    ptr,
+    ptr::{
+        self,
+        Pointee,
+        metadata,
+        from_raw_parts_mut,
+    },
+};
+use alloc::{
+    alloc::handle_alloc_error,
+    vec::Vec,
+    boxed::Box,
 };
 
-/// Size of a thin pointer.
-const THIN_PTR_SIZE: usize = size_of::<usize>();
+/// Fat-pointer metadata of a pointer to `T`, such as the length of a slice
+/// or the vtable of a trait object, which is `()` if the pointer isn't fat.
+pub(crate) type Meta<T> = <T as Pointee>::Metadata;
 
-/// Size of a fat pointer.
-const FAT_PTR_SIZE: usize = size_of::<usize>() * 2;
+/// Function which drops an element in-place, given the start address of the
+/// element and its fat pointer metadata.
+pub(crate) type DropHandler<T> = fn(*mut u8, Meta<T>);
 
-/// Whether a pointer to a type is a fat pointer.
-fn pointer_is_fat<T: ?Sized>() -> bool {
-    match size_of::<&T>() {
-        FAT_PTR_SIZE => true,
-        THIN_PTR_SIZE => false,
-        _ => panic!("unexpected pointer size: {}", size_of::<&T>())
+/// Round `n` up to a multiple of `align`.
+pub(crate) fn align_up(n: usize, align: usize) -> usize {
+    match n % align {
+        0 => n,
+        rem => n + (align - rem),
     }
 }
 
-/// Decomposed memory representation of a fat pointer.
-#[repr(C)]
-#[derive(Copy, Clone)]
-struct FatPtr<DataPtr> {
-    data: DataPtr,
-    meta: usize,
+/// Split a pointer into its data pointer and its fat pointer meta.
+pub(crate) fn ptr_into_parts<T: ?Sized>(p: *const T) -> (*const u8, Meta<T>) {
+    (p as *const u8, metadata(p))
 }
 
-type FatPtrConst = FatPtr<*const u8>;
-type FatPtrMut = FatPtr<*mut u8>;
-
-impl FatPtrConst {
-    #[inline(always)]
-    unsafe fn transmute_to_ref<'a, T: ?Sized>(self) -> &'a T {
-        transmute_copy::<FatPtrConst, &T>(&self)
-    }
-
-    #[inline(always)]
-    unsafe fn transmute_from_ptr<T: ?Sized>(p: *const T) -> Self {
-        transmute_copy::<*const T, FatPtrConst>(&p)
-    }
+/// Reassemble a pointer from the parts given by `ptr_into_parts`.
+pub(crate) fn ptr_from_parts<T: ?Sized>(data: *mut u8, meta: Meta<T>) -> *mut T {
+    from_raw_parts_mut::<T>(data, meta)
 }
 
-impl FatPtrMut {
-    #[inline(always)]
-    unsafe fn transmute_to_mut<'a, T: ?Sized>(self) -> &'a mut T {
-        transmute_copy::<FatPtrMut, &mut T>(&self)
-    }
-
-    #[inline(always)]
-    unsafe fn transmute_to_box<T: ?Sized>(self) -> Box<T> {
-        transmute_copy::<FatPtrMut, Box<T>>(&self)
-    }
+/// Move an element, given the parts of a pointer to it, into a new box
+/// allocated with `alloc`.
+///
+/// The box is allocated with the element's own layout, which is what the
+/// box deallocates with. A zero-sized element is not allocated at all, like
+/// in `Box::new`.
+pub(crate) unsafe fn move_into_box_in<T, A>(data: *const u8, meta: Meta<T>, alloc: A) -> Box<T, A>
+where
+    T: ?Sized,
+    A: Allocator,
+{
+    let layout = Layout::for_value_raw::<T>(ptr_from_parts::<T>(data as *mut u8, meta));
+    let heap_ptr: *mut u8 = match layout.size() {
+        // dangling, aligned pointer
+        0 => ptr::without_provenance_mut(layout.align()),
+        _ => match alloc.allocate(layout) {
+            Ok(heap_ptr) => heap_ptr.cast::<u8>().as_ptr(),
+            Err(AllocError) => handle_alloc_error(layout),
+        },
+    };
+    ptr::copy_nonoverlapping::<u8>(
+        // src:
+        data,
+        // dst:
+        heap_ptr,
+        // len:
+        layout.size(),
+    );
+    Box::from_raw_in(ptr_from_parts::<T>(heap_ptr, meta), alloc)
 }
 
-impl<T: ?Sized> HeteroSizedVec<T> {
+impl<T: ?Sized, A: Allocator> HeteroSizedVec<T, A> {
     /// Push an element onto the vector.
     pub fn push<E: HeteroSizedPush<T>>(&mut self, elem: E) {
+        self.debug_check();
         unsafe {
             // prevent double-free in panic
             let mut elem = ManuallyDrop::new(elem);
@@ -74,54 +98,40 @@ impl<T: ?Sized> HeteroSizedVec<T> {
             let elem_size:  usize  = elem.elem_size();
             let elem_align: usize  = elem.elem_align();
             let elem_ptr: *const T = elem.elem_ptr();
-            let elem_drop_handler: fn(*mut u8, usize) = elem.elem_drop_handler();
-
-            // push the fat pointer meta,
-            // handle the case that the pointer isn't actually fat
-            let elem_data_ptr: *const u8 = match pointer_is_fat::<T>() {
-                true => {
-                    let parts = FatPtrConst::transmute_from_ptr::<T>(elem_ptr);
-                    self.ptr_meta.push(parts.meta);
-                    parts.data
-                },
-                false => transmute_copy::<*const T, *const u8>(&elem_ptr),
+            let elem_drop_handler: Option<DropHandler<T>> = elem.elem_drop_handler();
+
+            // push the fat pointer meta
+            let (elem_data_ptr, meta) = ptr_into_parts::<T>(elem_ptr);
+            self.ptr_meta.push(meta);
+
+            // determine the start position in the elements storage,
+            // reusing freed space if the element fits somewhere
+            //
+            // zero-sized elements take no storage, so their offset is
+            // never used
+            let offset: usize = match elem_size {
+                0 => 0,
+                _ => self.alloc_bytes(elem_size, elem_align),
             };
 
-            // determine the start position in the elements storage
-            // and the amount of padding bytes to place before-hand
-            let mut to_reserve: usize = elem_size;
-            let mut offset: usize = self.storage.len();
-
-            if self.storage.len() % elem_align != 0 {
-                let padding: usize = (
-                    elem_align - (self.storage.len() % elem_align)
-                );
-
-                offset += padding;
-                to_reserve += padding;
-            }
-
             // add mem index and elem len
             self.mem_indices.push(offset);
             self.elems_size.push(elem_size);
 
+            // record the concrete type, if tracking
+            if let Some(ref mut elem_types) = self.elem_types {
+                elem_types.push(elem.elem_type());
+            }
+
             // write element to storage memory
-            // 1. this is really unsafe
-            // 2. it leaves the padding bytes as uninitialized
-            // 3. but i don't think that will actually trigged UB
-            // 4. because of... vector implementation details... :/
-            self.storage.reserve(to_reserve);
             ptr::copy_nonoverlapping::<u8>(
                 // src:
                 elem_data_ptr,
                 // dst:
-                self.storage.as_mut_ptr().offset(offset as isize),
+                self.storage.as_mut_ptr().add(offset),
                 // len:
                 elem_size,
             );
-            self.storage.set_len(
-                self.storage.len() + to_reserve
-            );
 
             // once we've finished reading from the elem, we can call
             // `outer_drop` on the wrapper, which will invalidate
@@ -129,182 +139,379 @@ impl<T: ?Sized> HeteroSizedVec<T> {
             elem.outer_drop();
 
             // add drop handler, now that all other state is properly created
+            self.needs_drop |= elem_drop_handler.is_some();
             self.drop_handlers.push(elem_drop_handler);
         }
     }
 
+    /// Pointer to the data of the element at `index`.
+    ///
+    /// A zero-sized element takes no storage, so this is a dangling pointer
+    /// aligned for it.
+    pub(crate) fn elem_data_ptr(&self, index: usize) -> *mut u8 {
+        let meta: Meta<T> = self.ptr_meta[index];
+        match self.elems_size[index] {
+            0 => {
+                let align: usize = unsafe {
+                    align_of_val_raw(ptr_from_parts::<T>(ptr::null_mut(), meta))
+                };
+                ptr::without_provenance_mut(align)
+            },
+            _ => unsafe {
+                self.storage.as_ptr().add(self.mem_indices[index]) as *mut u8
+            },
+        }
+    }
+
     /// Get element by index as reference.
     pub fn get(&self, index: usize) -> Option<&T> {
+        if index >= self.len() {
+            return None;
+        }
         unsafe {
-            let offset: usize = match self.mem_indices.get(index) {
-                Some(&offset) => offset,
-                None => return None,
-            };
-            let raw_ptr: *const u8 = self.storage.as_ptr()
-                .offset(offset as isize);
-
-            if pointer_is_fat::<T>() {
-                Some(FatPtrConst {
-                    data: raw_ptr,
-                    meta: *self.ptr_meta.get_unchecked(index),
-                }.transmute_to_ref::<T>())
-            } else {
-                Some(transmute_copy::<*const u8, &T>(&raw_ptr))
-            }
+            let raw_ptr: *mut u8 = self.elem_data_ptr(index);
+
+            Some(&*ptr_from_parts::<T>(raw_ptr, *self.ptr_meta.get_unchecked(index)))
         }
     }
 
     /// Get element by index as mutable reference.
     pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        self.debug_check();
+        self.elem_mut(index)
+    }
+
+    /// `get_mut`, without the integrity check.
+    pub(crate) fn elem_mut(&mut self, index: usize) -> Option<&mut T> {
+        if index >= self.len() {
+            return None;
+        }
         unsafe {
-            let offset: usize = match self.mem_indices.get(index) {
-                Some(&offset) => offset,
-                None => return None,
-            };
-            let raw_ptr: *mut u8 = self.storage.as_mut_ptr()
-                .offset(offset as isize);
-
-            if pointer_is_fat::<T>() {
-                Some(FatPtrMut {
-                    data: raw_ptr,
-                    meta: *self.ptr_meta.get_unchecked(index),
-                }.transmute_to_mut::<T>())
-            } else {
-                Some(transmute_copy::<*mut u8, &mut T>(&raw_ptr))
-            }
+            let raw_ptr: *mut u8 = self.elem_data_ptr(index);
+
+            Some(&mut *ptr_from_parts::<T>(raw_ptr, *self.ptr_meta.get_unchecked(index)))
         }
     }
 
-    /// Convert into a vector of boxes.
-    pub fn into_box_vec(self) -> Vec<Box<T>> {
-        unsafe {
-            if pointer_is_fat::<T>() {
-                self.into_boxed_vec_fat()
-            } else {
-                self.into_boxed_vec_thin()
+    /// Find a place in storage for `size` bytes aligned to `align`, and
+    /// return its offset.
+    ///
+    /// This reuses the first freed range that fits, or else grows the
+    /// storage.
+    pub(crate) unsafe fn alloc_bytes(&mut self, size: usize, align: usize) -> usize {
+        self.storage.realign(align);
+
+        // zero-sized allocations would only fragment the freed ranges
+        if size > 0 {
+            for i in 0..self.free_ranges.len() {
+                let (start, end) = self.free_ranges[i];
+                let offset: usize = align_up(start + self.guard_len, align);
+
+                if offset + size + self.guard_len <= end {
+                    // keep whatever is left on either side, apart from the
+                    // guard bytes, which become padding
+                    self.free_ranges.remove(i);
+                    if offset + size + self.guard_len < end {
+                        self.free_ranges.insert(i, (offset + size + self.guard_len, end));
+                    }
+                    if start < offset - self.guard_len {
+                        self.free_ranges.insert(i, (start, offset - self.guard_len));
+                    }
+                    for guard in &[offset - self.guard_len, offset + size] {
+                        for byte in &mut self.storage[*guard..*guard + self.guard_len] {
+                            *byte = PADDING_BYTE;
+                        }
+                    }
+                    return offset;
+                }
             }
         }
+
+        self.alloc_bytes_at_end(size, align)
     }
 
-    // `into_boxed_vec` case where `&T` is a fat pointer.
-    unsafe fn into_boxed_vec_fat(mut self) -> Vec<Box<T>> {
-        // clear the drop handlers now to prevent double-free in panic
-        self.drop_handlers.clear();
+    /// Grow the storage by `size` bytes aligned to `align`, and return their
+    /// offset.
+    ///
+    /// Unless empty, the storage ends with guard bytes after the last
+    /// element, which this keeps after the new bytes.
+    pub(crate) unsafe fn alloc_bytes_at_end(&mut self, size: usize, align: usize) -> usize {
+        self.storage.realign(align);
+        // the existing trailing guard, if any, guards the start of the new
+        // bytes
+        let offset: usize = align_up(self.storage.len().max(self.guard_len), align);
+
+        // initialize the padding bytes, so that storage holding only plain
+        // data is fully initialized
+        self.storage.resize(offset, PADDING_BYTE);
+
+        // this leaves the new bytes as uninitialized, until the element is
+        // written to them
+        self.storage.reserve(size + self.guard_len);
+        self.storage.set_len(offset + size);
+        self.storage.resize(offset + size + self.guard_len, PADDING_BYTE);
+
+        offset
+    }
+
+    /// Mark a range of storage as no longer occupied by an element, so that
+    /// it may be reused.
+    pub(crate) fn free_bytes(&mut self, start: usize, end: usize) {
+        if start == end {
+            return;
+        }
 
-        let mut boxed_vec: Vec<Box<T>> =
-            Vec::with_capacity(self.mem_indices.len());
+        if end + self.guard_len == self.storage.len() {
+            // nothing lives after this range, so shrink the storage instead,
+            // along with any freed range right before it
+            //
+            // the guard bytes before the range become the trailing guard
+            let mut new_len: usize = start;
+            if let Some(&(prev_start, prev_end)) = self.free_ranges.last() {
+                if prev_end == start {
+                    self.free_ranges.pop();
+                    new_len = prev_start;
+                }
+            }
+            self.storage.truncate(new_len);
+            return;
+        }
+
+        // insert in order, merging with adjacent ranges
+        let i: usize = self.free_ranges
+            .partition_point(|&(other_start, _)| other_start < start);
+        let merge_prev: bool = i > 0 && self.free_ranges[i - 1].1 == start;
+        let merge_next: bool =
+            i < self.free_ranges.len() && self.free_ranges[i].0 == end;
+
+        match (merge_prev, merge_next) {
+            (true, true) => {
+                self.free_ranges[i - 1].1 = self.free_ranges[i].1;
+                self.free_ranges.remove(i);
+            },
+            (true, false) => self.free_ranges[i - 1].1 = end,
+            (false, true) => self.free_ranges[i].0 = start,
+            (false, false) => self.free_ranges.insert(i, (start, end)),
+        }
+    }
 
-        // iterate through elements
-        for (offset, (size, meta)) in Iterator::zip(
-            self.mem_indices.iter().copied(),
-            Iterator::zip(
-                self.elems_size.iter().copied(),
-                self.ptr_meta.iter().copied(),
-            ),
-        ) {
+    /// Remove an element's entry without dropping it, and free its storage.
+    pub(crate) unsafe fn forget(&mut self, index: usize) {
+        self.debug_check();
+        let offset: usize = self.mem_indices.remove(index);
+        let size: usize = self.elems_size.remove(index);
+        self.drop_handlers.remove(index);
+        self.ptr_meta.remove(index);
+        if let Some(ref mut elem_types) = self.elem_types {
+            elem_types.remove(index);
+        }
 
-            // make the heap allocation
-            let heap_alloc: Box<[MaybeUninit<u8>]> =
-                Box::new_uninit_slice(size);
+        self.free_bytes(offset, offset + size);
+    }
 
-            // lower it into a raw heap data pointer
-            let heap_ptr: *mut u8 =
-                Box::into_raw(heap_alloc) as *mut u8;
+    /// Remove an element's entry without dropping it, and free its storage,
+    /// by moving the last element's entry into its place.
+    pub(crate) unsafe fn swap_forget(&mut self, index: usize) {
+        self.debug_check();
+        let offset: usize = self.mem_indices.swap_remove(index);
+        let size: usize = self.elems_size.swap_remove(index);
+        self.drop_handlers.swap_remove(index);
+        self.ptr_meta.swap_remove(index);
+        if let Some(ref mut elem_types) = self.elem_types {
+            elem_types.swap_remove(index);
+        }
 
-            // copy the element onto the heap
-            ptr::copy_nonoverlapping::<u8>(
-                // src:
-                self.storage.as_mut_ptr().offset(offset as isize),
-                // dst:
-                heap_ptr,
-                // len:
-                size,
-            );
+        self.free_bytes(offset, offset + size);
+    }
 
-            // produce the fat heap pointer as a box
-            let fat_box: Box<T> = FatPtrMut {
-                data: heap_ptr,
-                meta,
-            }.transmute_to_box::<T>();
+    // drop an element in-place, after removing it with `remove_fn`
+    unsafe fn remove_drop_with(
+        &mut self,
+        index: usize,
+        remove_fn: unsafe fn(&mut Self, usize),
+    ) {
+        let data: *mut u8 = self.elem_data_ptr(index);
+        let destructor: Option<DropHandler<T>> = self.drop_handlers[index];
+        let meta: Meta<T> = self.ptr_meta[index];
+
+        // forget before dropping, so that a panicking destructor causes a
+        // leak rather than a double-free
+        //
+        // this may shrink the storage, but not its allocation
+        remove_fn(self, index);
+        if let Some(destructor) = destructor {
+            destructor(data, meta);
+        }
+    }
 
-            // push to the vec
-            boxed_vec.push(fat_box);
+    /// Remove and drop an element by index, shifting all elements after it
+    /// down by one index.
+    ///
+    /// Its space in storage can be reused by later pushes.
+    ///
+    /// Panics if out of bounds.
+    pub fn remove(&mut self, index: usize) {
+        assert!(
+            index < self.len(),
+            "removal index (is {}) should be < len (is {})",
+            index, self.len(),
+        );
+        unsafe {
+            self.remove_drop_with(index, Self::forget);
         }
+    }
 
-        // done
-        boxed_vec
+    /// Drop an element in-place, and `swap_forget` it.
+    pub(crate) fn swap_remove_drop(&mut self, index: usize) {
+        unsafe {
+            self.remove_drop_with(index, Self::swap_forget);
+        }
     }
 
-    // `into_boxed_vec` case where `&T` is a thin pointer.
-    unsafe fn into_boxed_vec_thin(mut self) -> Vec<Box<T>> {
-        // clear the drop handlers now to prevent double-free in panic
-        self.drop_handlers.clear();
+    /// Remove and drop all elements, keeping the allocated capacity.
+    ///
+    /// If no element has a destructor, such as in a `HeteroSizedVec<str>`
+    /// or `HeteroSizedVec<[u8]>`, this takes constant time.
+    pub fn clear(&mut self) {
+        self.debug_check();
+        if self.needs_drop {
+            for index in 0..self.len() {
+                // disarm before dropping, so that a panicking destructor
+                // causes a leak rather than a double-free
+                if let Some(destructor) = self.drop_handlers[index].take() {
+                    destructor(self.elem_data_ptr(index), self.ptr_meta[index]);
+                }
+            }
+        }
 
-        let mut boxed_vec: Vec<Box<T>> =
-            Vec::with_capacity(self.mem_indices.len());
+        self.ptr_meta.clear();
+        self.mem_indices.clear();
+        self.drop_handlers.clear();
+        self.elems_size.clear();
+        if let Some(ref mut elem_types) = self.elem_types {
+            elem_types.clear();
+        }
+        self.free_ranges.clear();
+        self.storage.truncate(0);
+        self.needs_drop = false;
+    }
 
-        // iterate through elements
-        for (offset, size) in Iterator::zip(
-            self.mem_indices.iter().copied(),
-            self.elems_size.iter().copied(),
-        ) {
-            // make the heap allocation
-            let heap_alloc: Box<[MaybeUninit<u8>]> =
-                Box::new_uninit_slice(size);
+    /// Pack the elements into a new storage allocation, removing any space
+    /// freed by removed elements.
+    ///
+    /// The elements stay in the order they are in storage, which need not be
+    /// index order, so that the storage never grows.
+    ///
+    /// Returns the number of bytes by which the storage shrank.
+    pub fn defragment(&mut self) -> usize
+    where
+        A: Clone,
+    {
+        self.debug_check();
+        unsafe {
+            let old_len: usize = self.storage.len();
+
+            // lay out the elements in the order of their current offsets, so
+            // that each only moves towards the start of storage
+            //
+            // zero-sized elements take no storage, so keep offset 0
+            let mut order: Vec<usize, A> = Vec::with_capacity_in(
+                self.len(),
+                self.allocator().clone(),
+            );
+            order.extend((0..self.len()).filter(|&index| self.elems_size[index] > 0));
+            order.sort_unstable_by_key(|&index| self.mem_indices[index]);
 
-            // lower it into a raw heap data pointer
-            let heap_ptr: *mut u8 =
-                Box::into_raw(heap_alloc) as *mut u8;
+            let mut new_indices: Vec<usize, A> = Vec::with_capacity_in(
+                self.len(),
+                self.allocator().clone(),
+            );
+            new_indices.resize(self.len(), 0);
+            let mut new_len: usize = 0;
+            for &index in &order {
+                let offset: usize = align_up(new_len + self.guard_len, align_of_val(&self[index]));
+                new_indices[index] = offset;
+                new_len = offset + self.elems_size[index];
+            }
+            // keep a trailing guard after the last element
+            if new_len > 0 {
+                new_len += self.guard_len;
+            }
 
-            // copy the element onto the heap
-            ptr::copy_nonoverlapping::<u8>(
-                // src:
-                self.storage.as_mut_ptr().offset(offset as isize),
-                // dst:
-                heap_ptr,
-                // len:
-                size,
+            // move them there, initializing the padding
+            let mut packed: Storage<A> = Storage::with_capacity_in(
+                new_len,
+                self.storage.align(),
+                self.allocator().clone(),
             );
+            packed.resize(new_len, PADDING_BYTE);
+            for &index in &order {
+                ptr::copy_nonoverlapping::<u8>(
+                    // src:
+                    self.storage.as_ptr().add(self.mem_indices[index]),
+                    // dst:
+                    packed.as_mut_ptr().add(new_indices[index]),
+                    // len:
+                    self.elems_size[index],
+                );
+            }
 
-            // make the heap data pointer typed
-            let thin_box: Box<T> = transmute_copy::<*mut u8, Box<T>>(&heap_ptr);
+            self.mem_indices = new_indices;
+            self.storage = packed;
+            self.free_ranges.clear();
 
-            // push to the vec
-            boxed_vec.push(thin_box);
+            old_len - self.storage.len()
         }
+    }
+
+    /// Convert into a vector of boxes.
+    ///
+    /// The boxes are allocated with the vector's allocator.
+    pub fn into_box_vec(mut self) -> Vec<Box<T, A>>
+    where
+        A: Clone,
+    {
+        self.debug_check();
+        unsafe {
+            // disarm the drop handlers now to prevent double-free in panic
+            //
+            // they are not cleared, so that the metadata stays consistent
+            self.needs_drop = false;
+
+            let mut boxed_vec: Vec<Box<T, A>> =
+                Vec::with_capacity(self.mem_indices.len());
+
+            // move each element into a box of its own layout
+            for index in 0..self.len() {
+                boxed_vec.push(move_into_box_in::<T, A>(
+                    self.elem_data_ptr(index),
+                    self.ptr_meta[index],
+                    self.allocator().clone(),
+                ));
+            }
 
-        boxed_vec
+            boxed_vec
+        }
     }
 }
 
-impl<T: ?Sized> Drop for HeteroSizedVec<T> {
+impl<T: ?Sized, A: Allocator> Drop for HeteroSizedVec<T, A> {
     fn drop(&mut self) {
-        // drop elements
-        unsafe {
-            if pointer_is_fat::<T>() {
-                for (offset, (destructor, meta)) in Iterator::zip(
-                    self.mem_indices.iter().copied(),
-                    Iterator::zip(
-                        self.drop_handlers.iter().copied(),
-                        self.ptr_meta.iter().copied(),
-                    ),
-                ) {
-                    let ptr: *mut u8 = self.storage.as_mut_ptr()
-                        .offset(offset as isize);
-
-                    destructor(ptr, meta);
-                }
-            } else {
-                for (offset, destructor) in Iterator::zip(
-                    self.mem_indices.iter().copied(),
-                    self.drop_handlers.iter().copied(),
-                ) {
-                    let ptr: *mut u8 = self.storage.as_mut_ptr()
-                        .offset(offset as isize);
-
-                    destructor(ptr, 0);
+        // a failed check while already unwinding would abort
+        #[cfg(feature = "std")]
+        if !std::thread::panicking() {
+            self.debug_check();
+        }
+        #[cfg(not(feature = "std"))]
+        self.debug_check();
+
+        // drop elements, unless none have a destructor
+        if self.needs_drop {
+            for index in 0..self.len() {
+                if let Some(destructor) = self.drop_handlers[index] {
+                    destructor(self.elem_data_ptr(index), self.ptr_meta[index]);
                 }
             }
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,114 @@
+
+use crate::{
+    HeteroSizedVec,
+    pushable::HeteroSizedPush,
+    elem_type::ElemType,
+};
+
+use core::{
+    alloc::Allocator,
+    mem::{
+        size_of,
+        align_of,
+    },
+    marker::PhantomData,
+    ptr::slice_from_raw_parts,
+};
+
+/// Marker for plain-data types, which can be read from and written to raw
+/// bytes.
+///
+/// # Safety
+///
+/// The type must have no padding bytes, every bit pattern must be a valid
+/// value of it, and it must not contain pointers or references.
+pub unsafe trait HeteroPod: Copy + 'static {}
+
+macro_rules! pod_impls {
+    ($($t:ty),*)=>{$(
+        unsafe impl HeteroPod for $t {}
+    )*};
+}
+
+pod_impls!(
+    u8, u16, u32, u64, u128, usize,
+    i8, i16, i32, i64, i128, isize,
+    f32, f64
+);
+
+unsafe impl<I: HeteroPod, const N: usize> HeteroPod for [I; N] {}
+
+// a slice element of plain data, given as bytes
+struct PodBytes<'a, I> {
+    bytes: &'a [u8],
+    p: PhantomData<I>,
+}
+
+unsafe impl<'a, I: HeteroPod> HeteroSizedPush<[I]> for PodBytes<'a, I> {
+    unsafe fn elem_size(&self) -> usize {
+        self.bytes.len()
+    }
+
+    unsafe fn elem_align(&self) -> usize {
+        align_of::<I>()
+    }
+
+    unsafe fn elem_ptr(&self) -> *const [I] {
+        // this may be misaligned, which the trait allows, as it's only read
+        // as bytes
+        slice_from_raw_parts(
+            self.bytes.as_ptr() as *const I,
+            self.bytes.len() / size_of::<I>(),
+        )
+    }
+
+    unsafe fn elem_drop_handler(&self) -> Option<fn(*mut u8, usize)> {
+        None // plain data has no destructor
+    }
+
+    unsafe fn elem_type(&self) -> ElemType {
+        ElemType::of::<[I]>()
+    }
+
+    unsafe fn outer_drop(&mut self) {}
+}
+
+impl<I: HeteroPod, A: Allocator> HeteroSizedVec<[I], A> {
+    /// View the entire storage as bytes.
+    ///
+    /// This includes the padding between elements, which is zeroed (or
+    /// filled with guard bytes, with the `debug-checks` feature), and space
+    /// left by removed elements.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.storage[..]
+    }
+
+    /// Push an element whose items are read from `bytes`, which need not be
+    /// aligned.
+    ///
+    /// Panics if the length of `bytes` is not a multiple of the size of `I`,
+    /// or if `I` is zero-sized.
+    pub fn extend_from_bytes(&mut self, bytes: &[u8]) {
+        assert!(size_of::<I>() > 0, "cannot read zero-sized items from bytes");
+        assert!(
+            bytes.len().is_multiple_of(size_of::<I>()),
+            "length {} is not a multiple of item size {}",
+            bytes.len(),
+            size_of::<I>(),
+        );
+        self.push(PodBytes::<I> {
+            bytes,
+            p: PhantomData,
+        });
+    }
+}
+
+impl<A: Allocator> HeteroSizedVec<str, A> {
+    /// View the entire storage as bytes.
+    ///
+    /// This includes space left by removed elements, so it may not be valid
+    /// UTF-8.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.storage[..]
+    }
+}
@@ -1,37 +1,133 @@
 
-use std::{
+use crate::{
+    elem_type::ElemType,
+    memory::DropHandler,
+};
+
+use core::{
     mem::{
         size_of,
+        size_of_val,
         align_of,
-        transmute,
-        transmute_copy,
-        ManuallyDrop,
+        needs_drop,
     },
     ptr::{
+        self,
+        Pointee,
         drop_in_place,
         slice_from_raw_parts_mut,
     },
     marker::Unsize,
 };
+use alloc::{
+    alloc::{
+        dealloc,
+        Layout,
+    },
+    vec::Vec,
+    boxed::Box,
+};
 
 /// Types that can be pushed onto a `HeteroSizedVec`.
 ///
 /// Not meant to be implemented outside of the `heterovec` crate.
+///
+/// # Safety
+///
+/// Containers push the element by copying its bytes to a place aligned to
+/// `elem_align`, then calling `outer_drop`, and from then on own the copy.
+/// So `elem_ptr` must point to the `elem_size` bytes of an element which is
+/// valid after being moved, and whose ownership `outer_drop` gives up. It's
+/// only read as bytes, so it need not be aligned.
+///
+/// Containers which track types cast elements to the type named by
+/// `elem_type`, so it must be either the exact concrete type of the element,
+/// or the default of `T` itself.
 pub unsafe trait HeteroSizedPush<T: ?Sized> {
+    /// Size in bytes of the element.
+    ///
+    /// # Safety
+    ///
+    /// Must not be called after `outer_drop`.
     unsafe fn elem_size(&self) -> usize;
 
+    /// Alignment of the element.
+    ///
+    /// # Safety
+    ///
+    /// Must not be called after `outer_drop`.
     unsafe fn elem_align(&self) -> usize;
 
+    /// Pointer to the element, which may be misaligned.
+    ///
+    /// # Safety
+    ///
+    /// The pointer is invalidated by `outer_drop`. The element must not be
+    /// dropped through it, since the caller takes ownership of a copy.
     unsafe fn elem_ptr(&self) -> *const T;
 
-    unsafe fn elem_drop_handler(&self) -> fn(*mut u8, usize);
+    /// Function which drops the element in-place, given the start address
+    /// of the element and its fat pointer metadata.
+    ///
+    /// This is `None` if the element has no destructor, so that containers
+    /// can skip dropping it.
+    ///
+    /// # Safety
+    ///
+    /// The function may only be called once, on a copy of the element which
+    /// the caller owns.
+    unsafe fn elem_drop_handler(&self) -> Option<fn(*mut u8, <T as Pointee>::Metadata)>;
+
+    /// The concrete type of the element, for vectors which track types.
+    ///
+    /// This defaults to `T` itself, as if the concrete type were unknown,
+    /// which typed access to the elements never matches. An override must
+    /// return the exact concrete type of the element, as required by the
+    /// trait's safety contract.
+    ///
+    /// # Safety
+    ///
+    /// Must not be called after `outer_drop`.
+    unsafe fn elem_type(&self) -> ElemType {
+        ElemType::of::<T>()
+    }
 
     /// Assume that ownership of the pointee has been taken through unsafe
     /// means, but if there is some destructable wrapper around that
     /// (eg. a `Box`), clean that up, but without dropping the inner element.
+    ///
+    /// # Safety
+    ///
+    /// Must be called exactly once, after the element has been copied out,
+    /// and nothing else may be called afterwards.
     unsafe fn outer_drop(&mut self);
 }
 
+/// Drop handler for a `[I]` element.
+///
+/// This relies on the fat pointer meta in an array slice being the length in
+/// elements.
+pub(crate) fn drop_slice<I>(start: *mut u8, len: usize) {
+    unsafe {
+        // drop each element
+        let elems: &mut [I] = &mut *slice_from_raw_parts_mut(
+            start as *mut I,
+            len,
+        );
+        for elem in elems {
+            drop_in_place(elem);
+        }
+    }
+}
+
+/// Drop handler for a `[I]` element, if `I` has a destructor.
+pub(crate) fn slice_drop_handler<I>() -> Option<fn(*mut u8, usize)> {
+    match needs_drop::<I>() {
+        true => Some(drop_slice::<I>),
+        false => None,
+    }
+}
+
 /// Used to directly push an element onto a `HeteroSizedVec` from the stack.
 pub struct InPlace<E>(pub E);
 
@@ -48,21 +144,25 @@ unsafe impl<T: ?Sized, E: Unsize<T>> HeteroSizedPush<T> for InPlace<E> {
         &self.0 as &T as *const T
     }
 
-    unsafe fn elem_drop_handler(&self) -> fn(*mut u8, usize) {
-        |data, _| {
-            drop_in_place(transmute::<
-                *mut u8,
-                &mut E,
-            >(data));
+    unsafe fn elem_drop_handler(&self) -> Option<DropHandler<T>> {
+        match needs_drop::<E>() {
+            true => Some(|data, _| unsafe {
+                drop_in_place(data as *mut E);
+            }),
+            false => None,
         }
     }
 
+    unsafe fn elem_type(&self) -> ElemType {
+        ElemType::of::<E>()
+    }
+
     unsafe fn outer_drop(&mut self) {}
 }
 
-unsafe impl<'a, I: Copy> HeteroSizedPush<[I]> for &'a [I] {
+unsafe impl<I: Copy> HeteroSizedPush<[I]> for &[I] {
     unsafe fn elem_size(&self) -> usize {
-        self.len() * size_of::<I>()
+        size_of_val(*self)
     }
 
     unsafe fn elem_align(&self) -> usize {
@@ -73,8 +173,12 @@ unsafe impl<'a, I: Copy> HeteroSizedPush<[I]> for &'a [I] {
         *self as *const [I]
     }
 
-    unsafe fn elem_drop_handler(&self) -> fn(*mut u8, usize) {
-        |_, _| () // we are copy, so no need to drop
+    unsafe fn elem_drop_handler(&self) -> Option<fn(*mut u8, usize)> {
+        None // we are copy, so no need to drop
+    }
+
+    unsafe fn elem_type(&self) -> ElemType {
+        ElemType::of::<[I]>()
     }
 
     unsafe fn outer_drop(&mut self) {}
@@ -93,31 +197,22 @@ unsafe impl<I> HeteroSizedPush<[I]> for Vec<I> {
         self.as_slice() as *const [I]
     }
 
-    unsafe fn elem_drop_handler(&self) -> fn(*mut u8, usize) {
-        // this relies on the fat pointer meta in an array slice being the length in elements
-        |start, len| {
-            // drop each element
-            let elems: &mut [I] = &mut *slice_from_raw_parts_mut(
-                start as *mut I,
-                len,
-            );
-            for elem in elems {
-                drop_in_place(elem);
-            }
-        }
+    unsafe fn elem_drop_handler(&self) -> Option<fn(*mut u8, usize)> {
+        slice_drop_handler::<I>()
     }
 
-    unsafe fn outer_drop(&mut self) {
-        // this is similar to how we are handling the box
+    unsafe fn elem_type(&self) -> ElemType {
+        ElemType::of::<[I]>()
+    }
 
-        drop(transmute_copy::<
-            Vec<I>,
-            Vec<ManuallyDrop<I>>,
-        >);
+    unsafe fn outer_drop(&mut self) {
+        // free the buffer, without dropping the items
+        self.set_len(0);
+        drop_in_place(self);
     }
 }
 
-unsafe impl<'a> HeteroSizedPush<str> for &'a str {
+unsafe impl HeteroSizedPush<str> for &str {
     unsafe fn elem_size(&self) -> usize {
         (*self).len()
     }
@@ -130,8 +225,12 @@ unsafe impl<'a> HeteroSizedPush<str> for &'a str {
         *self as *const str
     }
 
-    unsafe fn elem_drop_handler(&self) -> fn(*mut u8, usize) {
-        |_, _| () // no destructor needed for str
+    unsafe fn elem_drop_handler(&self) -> Option<fn(*mut u8, usize)> {
+        None // no destructor needed for str
+    }
+
+    unsafe fn elem_type(&self) -> ElemType {
+        ElemType::of::<str>()
     }
 
     unsafe fn outer_drop(&mut self) {}
@@ -150,16 +249,22 @@ unsafe impl<T: ?Sized> HeteroSizedPush<T> for Box<dyn HeteroSizedPush<T>> {
         Box::as_ref(self).elem_ptr()
     }
 
-    unsafe fn elem_drop_handler(&self) -> fn(*mut u8, usize) {
+    unsafe fn elem_drop_handler(&self) -> Option<DropHandler<T>> {
         Box::as_ref(self).elem_drop_handler()
     }
 
-    unsafe fn outer_drop(&mut self) {
-        // this feels deeply disturbing
+    unsafe fn elem_type(&self) -> ElemType {
+        Box::as_ref(self).elem_type()
+    }
 
-        drop(transmute_copy::<
-            Box<dyn HeteroSizedPush<T>>,
-            Box<ManuallyDrop<dyn HeteroSizedPush<T>>>,
-        >(self));
+    unsafe fn outer_drop(&mut self) {
+        // clean up the wrapper inside the box, then free the box without
+        // dropping what's inside it
+        Box::as_mut(self).outer_drop();
+        let layout = Layout::for_value::<dyn HeteroSizedPush<T>>(&**self);
+        let inner: *mut dyn HeteroSizedPush<T> = Box::into_raw(ptr::read(self));
+        if layout.size() > 0 {
+            dealloc(inner as *mut u8, layout);
+        }
     }
 }
\ No newline at end of file
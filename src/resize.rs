@@ -0,0 +1,263 @@
+
+use crate::{
+    HeteroSizedVec,
+    pushable::slice_drop_handler,
+    elem_type::ElemType,
+    integrity::PADDING_BYTE,
+};
+
+use core::{
+    alloc::Allocator,
+    mem::{
+        size_of,
+        size_of_val,
+        align_of_val,
+        needs_drop,
+        ManuallyDrop,
+    },
+    ptr::{
+        self,
+        Pointee,
+        drop_in_place,
+        slice_from_raw_parts_mut,
+    },
+    ops::Range,
+};
+
+// fills bytes with `PADDING_BYTE` when dropped
+struct FillOnDrop(*mut u8, usize);
+
+impl Drop for FillOnDrop {
+    fn drop(&mut self) {
+        unsafe {
+            ptr::write_bytes(self.0, PADDING_BYTE, self.1);
+        }
+    }
+}
+
+// these are only for slices and `str`, where the fat pointer meta is a length
+impl<T: ?Sized + Pointee<Metadata = usize>, A: Allocator> HeteroSizedVec<T, A> {
+    fn last_index(&self) -> usize {
+        match self.len() {
+            0 => panic!("HeteroSizedVec has no last element"),
+            len => len - 1,
+        }
+    }
+
+    // append raw bytes onto the end of the last element, and increase its
+    // fat pointer meta by `meta_delta`
+    //
+    // the caller must ensure that the bytes are a valid continuation of the
+    // element, and that the element's drop handler will handle the new meta
+    unsafe fn last_extend_raw(
+        &mut self,
+        src: *const u8,
+        len: usize,
+        meta_delta: usize,
+    ) {
+        self.debug_check();
+        let index = self.last_index();
+        let offset: usize = self.mem_indices[index];
+        let size: usize = self.elems_size[index];
+
+        // zero-sized items take no storage
+        if len == 0 {
+            self.ptr_meta[index] += meta_delta;
+            return;
+        }
+
+        // the last element may have been pushed into freed space, or be
+        // zero-sized and so not in storage at all, in which case it must
+        // first be moved to the end of storage to grow
+        if size == 0 || offset + size + self.guard_len != self.storage.len() {
+            let align: usize = align_of_val(&self[index]);
+            let new_offset: usize = self.alloc_bytes_at_end(size, align);
+            ptr::copy_nonoverlapping::<u8>(
+                // src:
+                self.storage.as_ptr().add(offset),
+                // dst:
+                self.storage.as_mut_ptr().add(new_offset),
+                // len:
+                size,
+            );
+            self.mem_indices[index] = new_offset;
+            self.free_bytes(offset, offset + size);
+        }
+
+        // the new bytes overwrite the trailing guard, which is then put back
+        // after them
+        let end: usize = self.mem_indices[index] + size;
+        self.storage.reserve(len);
+        ptr::copy_nonoverlapping::<u8>(
+            // src:
+            src,
+            // dst:
+            self.storage.as_mut_ptr().add(end),
+            // len:
+            len,
+        );
+        self.storage.set_len(end + len);
+        self.storage.resize(end + len + self.guard_len, PADDING_BYTE);
+
+        self.elems_size[index] += len;
+        self.ptr_meta[index] += meta_delta;
+    }
+
+    // shrink the last element to `size` bytes and `meta`, freeing the rest
+    // of its storage
+    //
+    // this does not drop anything, so neither does it write the guard bytes
+    // after the element, which may still hold items to be dropped. instead it
+    // returns their range, for the caller to fill with `PADDING_BYTE`
+    fn last_shrink_raw(&mut self, size: usize, meta: usize) -> Range<usize> {
+        self.debug_check();
+        let index = self.last_index();
+        let offset: usize = self.mem_indices[index];
+        let old_size: usize = self.elems_size[index];
+
+        self.ptr_meta[index] = meta;
+        self.elems_size[index] = size;
+
+        // a zero-sized element takes no storage, so has no offset
+        if size == 0 {
+            self.mem_indices[index] = 0;
+            self.free_bytes(offset, offset + old_size);
+            return 0..0;
+        }
+
+        // free what's left after the new guard, or shrink the storage if the
+        // element is at its end
+        let end: usize = offset + size;
+        match offset + old_size + self.guard_len == self.storage.len() {
+            true => self.storage.truncate(end + self.guard_len),
+            false => self.free_bytes(
+                (end + self.guard_len).min(offset + old_size),
+                offset + old_size,
+            ),
+        }
+        end..end + self.guard_len
+    }
+}
+
+impl<I, A: Allocator> HeteroSizedVec<[I], A> {
+    // the element may have been pushed as a fixed-size array, in which case
+    // its drop handler and concrete type don't allow for resizing it
+    fn last_unfix_len(&mut self, index: usize) {
+        self.drop_handlers[index] = slice_drop_handler::<I>();
+        self.needs_drop |= needs_drop::<I>();
+        if let Some(ref mut elem_types) = self.elem_types {
+            elem_types[index] = ElemType::of::<[I]>();
+        }
+    }
+
+    /// Append clones of `items` to the last element, in-place.
+    ///
+    /// Panics if the vector is empty.
+    pub fn last_extend_from_slice(&mut self, items: &[I])
+    where
+        I: Clone,
+    {
+        // panics if empty
+        self.last_index();
+        self.storage.reserve(size_of_val(items));
+
+        for item in items {
+            self.last_push_item(item.clone());
+        }
+    }
+
+    /// Append an item to the last element, in-place.
+    ///
+    /// Panics if the vector is empty.
+    pub fn last_push_item(&mut self, item: I) {
+        unsafe {
+            let index = self.last_index();
+
+            self.last_unfix_len(index);
+
+            let item = ManuallyDrop::new(item);
+            self.last_extend_raw(
+                &*item as *const I as *const u8,
+                size_of::<I>(),
+                1,
+            );
+        }
+    }
+
+    /// Shorten the last element to `len` items, dropping the rest in-place.
+    ///
+    /// Has no effect if the last element is already that short.
+    ///
+    /// Panics if the vector is empty.
+    pub fn last_truncate(&mut self, len: usize) {
+        unsafe {
+            let index = self.last_index();
+            let old_len: usize = self.ptr_meta[index];
+
+            if len >= old_len {
+                return;
+            }
+
+            self.last_unfix_len(index);
+
+            // shrink before dropping, so that a panicking destructor
+            // causes a leak rather than a double-free
+            //
+            // this may shrink the storage, but not its allocation
+            let data: *mut u8 = self.elem_data_ptr(index);
+            let guard: Range<usize> = self.last_shrink_raw(len * size_of::<I>(), len);
+
+            // write the guard over the start of the tail once it's dropped,
+            // even if that panics
+            let _fill = FillOnDrop(self.storage.as_mut_ptr().add(guard.start), guard.len());
+
+            let tail: *mut [I] = slice_from_raw_parts_mut(
+                data.add(len * size_of::<I>()) as *mut I,
+                old_len - len,
+            );
+            drop_in_place(tail);
+        }
+    }
+}
+
+impl<A: Allocator> HeteroSizedVec<str, A> {
+    /// Append a string to the last element, in-place.
+    ///
+    /// Panics if the vector is empty.
+    pub fn last_extend_from_slice(&mut self, s: &str) {
+        unsafe {
+            self.last_extend_raw(s.as_ptr(), s.len(), s.len());
+        }
+    }
+
+    /// Append a char to the last element, in-place.
+    ///
+    /// Panics if the vector is empty.
+    pub fn last_push_item(&mut self, c: char) {
+        self.last_extend_from_slice(c.encode_utf8(&mut [0; 4]));
+    }
+
+    /// Shorten the last element to `len` bytes, in-place.
+    ///
+    /// Has no effect if the last element is already that short.
+    ///
+    /// Panics if the vector is empty, or if `len` does not lie on a char
+    /// boundary.
+    pub fn last_truncate(&mut self, len: usize) {
+        let index = self.last_index();
+        let elem: &str = &self[index];
+
+        if len >= elem.len() {
+            return;
+        }
+        assert!(
+            elem.is_char_boundary(len),
+            "new length does not lie on a char boundary",
+        );
+
+        let guard: Range<usize> = self.last_shrink_raw(len, len);
+        for byte in &mut self.storage[guard] {
+            *byte = PADDING_BYTE;
+        }
+    }
+}
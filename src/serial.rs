@@ -0,0 +1,613 @@
+
+use crate::pod::HeteroPod;
+#[cfg(feature = "std")]
+use crate::{
+    HeteroSizedVec,
+    memory::align_up,
+};
+
+use core::{
+    convert::TryFrom,
+    ops::{
+        Index,
+        Range,
+    },
+    mem::{
+        size_of,
+        align_of,
+    },
+    marker::PhantomData,
+    ptr::slice_from_raw_parts,
+    str,
+    fmt::{
+        self,
+        Display,
+        Formatter,
+    },
+};
+#[cfg(feature = "std")]
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use core::{
+    alloc::Allocator,
+    mem::size_of_val,
+    ptr::Pointee,
+    slice,
+};
+#[cfg(feature = "std")]
+use std::io::{
+    self,
+    Read,
+    Write,
+};
+
+/// Magic bytes at the start of serialized data.
+const MAGIC: [u8; 4] = *b"HSVC";
+
+/// Version of the serialization format, which is written in the header.
+pub const FORMAT_VERSION: u8 = 1;
+
+/// Size of the header.
+const HEADER_LEN: usize = 32;
+
+const KIND_STR: u8 = 0;
+const KIND_SLICE: u8 = 1;
+
+const LITTLE_ENDIAN: u8 = 0;
+const BIG_ENDIAN: u8 = 1;
+
+/// Byte order of this target, as written in the header.
+fn native_endian() -> u8 {
+    match cfg!(target_endian = "little") {
+        true => LITTLE_ENDIAN,
+        false => BIG_ENDIAN,
+    }
+}
+
+/// Error in serialized data.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The data doesn't start with the magic bytes.
+    BadMagic,
+    /// The data was written with an unsupported version of the format.
+    UnsupportedVersion(u8),
+    /// The data was written with a different element type.
+    ElemTypeMismatch,
+    /// The data was written on a target with a different byte order.
+    EndiannessMismatch,
+    /// The data is shorter than its header says.
+    Truncated,
+    /// An element, by index, is out of bounds, misaligned within the data,
+    /// or overlaps the element before it.
+    BadElem(usize),
+    /// A `str` element, by index, is not valid UTF-8.
+    InvalidUtf8(usize),
+    /// An element, by index, is not aligned in memory, because the data
+    /// itself is not.
+    Misaligned(usize),
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            DecodeError::BadMagic => f.write_str("bad magic bytes"),
+            DecodeError::UnsupportedVersion(version) =>
+                write!(f, "unsupported format version {}", version),
+            DecodeError::ElemTypeMismatch => f.write_str("element type mismatch"),
+            DecodeError::EndiannessMismatch => f.write_str("endianness mismatch"),
+            DecodeError::Truncated => f.write_str("data is truncated"),
+            DecodeError::BadElem(i) => write!(f, "element {} is out of bounds", i),
+            DecodeError::InvalidUtf8(i) => write!(f, "element {} is not valid UTF-8", i),
+            DecodeError::Misaligned(i) => write!(f, "element {} is misaligned", i),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DecodeError {}
+
+#[cfg(feature = "std")]
+impl From<DecodeError> for io::Error {
+    fn from(e: DecodeError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, e)
+    }
+}
+
+/// Decoded header.
+struct Header {
+    kind: u8,
+    endian: u8,
+    item_size: usize,
+    item_align: usize,
+    count: usize,
+    data_len: usize,
+}
+
+impl Header {
+    #[cfg(feature = "std")]
+    fn encode(&self) -> [u8; HEADER_LEN] {
+        let mut bytes = [0; HEADER_LEN];
+        bytes[0..4].copy_from_slice(&MAGIC);
+        bytes[4] = FORMAT_VERSION;
+        bytes[5] = self.endian;
+        bytes[6] = self.kind;
+        bytes[8..12].copy_from_slice(&(self.item_size as u32).to_le_bytes());
+        bytes[12..16].copy_from_slice(&(self.item_align as u32).to_le_bytes());
+        bytes[16..24].copy_from_slice(&(self.count as u64).to_le_bytes());
+        bytes[24..32].copy_from_slice(&(self.data_len as u64).to_le_bytes());
+        bytes
+    }
+
+    /// Decode a header, and check that it's for the given element type.
+    fn decode(
+        bytes: &[u8],
+        kind: u8,
+        item_size: usize,
+        item_align: usize,
+    ) -> Result<Self, DecodeError> {
+        if bytes.len() < HEADER_LEN {
+            return Err(DecodeError::Truncated);
+        }
+        if bytes[0..4] != MAGIC {
+            return Err(DecodeError::BadMagic);
+        }
+        if bytes[4] != FORMAT_VERSION {
+            return Err(DecodeError::UnsupportedVersion(bytes[4]));
+        }
+        let header = Header {
+            endian: bytes[5],
+            kind: bytes[6],
+            item_size: read_u32(&bytes[8..12]) as usize,
+            item_align: read_u32(&bytes[12..16]) as usize,
+            count: read_u64(&bytes[16..24])?,
+            data_len: read_u64(&bytes[24..32])?,
+        };
+        if
+            header.kind != kind
+            || header.item_size != item_size
+            || header.item_align != item_align
+        {
+            return Err(DecodeError::ElemTypeMismatch);
+        }
+        // the order of bytes within an item only matters if it has several
+        if item_size > 1 && header.endian != native_endian() {
+            return Err(DecodeError::EndiannessMismatch);
+        }
+        Ok(header)
+    }
+
+    /// Length of the offsets and lengths tables together.
+    fn tables_len(&self) -> Result<usize, DecodeError> {
+        self.count.checked_mul(16).ok_or(DecodeError::Truncated)
+    }
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    let mut buf = [0; 4];
+    buf.copy_from_slice(bytes);
+    u32::from_le_bytes(buf)
+}
+
+fn read_u64(bytes: &[u8]) -> Result<usize, DecodeError> {
+    let mut buf = [0; 8];
+    buf.copy_from_slice(bytes);
+    usize::try_from(u64::from_le_bytes(buf)).map_err(|_| DecodeError::Truncated)
+}
+
+/// Offset and length of each element, from the offsets table followed by
+/// the lengths table.
+fn table_entries(
+    tables: &[u8],
+    count: usize,
+) -> impl Iterator<Item=Result<(usize, usize), DecodeError>> + '_ {
+    let (offsets, lens) = tables.split_at(count * 8);
+    offsets.chunks(8)
+        .zip(lens.chunks(8))
+        .map(|(offset, len)| Ok((read_u64(offset)?, read_u64(len)?)))
+}
+
+/// Check that the elements are within the data, aligned, in order, and
+/// don't overlap.
+fn check_elems(tables: &[u8], header: &Header) -> Result<(), DecodeError> {
+    let mut prev_end: usize = 0;
+    for (i, entry) in table_entries(tables, header.count).enumerate() {
+        let (offset, len) = entry?;
+        let end: usize = len.checked_mul(header.item_size)
+            .and_then(|size| offset.checked_add(size))
+            .ok_or(DecodeError::BadElem(i))?;
+        if
+            offset < prev_end
+            || end > header.data_len
+            || offset % header.item_align != 0
+        {
+            return Err(DecodeError::BadElem(i));
+        }
+        prev_end = end;
+    }
+    Ok(())
+}
+
+/// Decode the offsets and lengths tables.
+#[cfg(feature = "std")]
+fn decode_tables(
+    tables: &[u8],
+    header: &Header,
+) -> Result<(Vec<usize>, Vec<usize>), DecodeError> {
+    check_elems(tables, header)?;
+    table_entries(tables, header.count).collect()
+}
+
+/// Write elements, given as their bytes and lengths.
+#[cfg(feature = "std")]
+fn write_elems<W: Write>(
+    mut write: W,
+    kind: u8,
+    item_size: usize,
+    item_align: usize,
+    elems: &[(&[u8], usize)],
+) -> io::Result<()> {
+    // pack the elements
+    let mut offsets: Vec<usize> = Vec::with_capacity(elems.len());
+    let mut data_len: usize = 0;
+    for &(bytes, _) in elems {
+        let offset: usize = align_up(data_len, item_align);
+        offsets.push(offset);
+        data_len = offset + bytes.len();
+    }
+
+    let header = Header {
+        kind,
+        endian: native_endian(),
+        item_size,
+        item_align,
+        count: elems.len(),
+        data_len,
+    };
+    write.write_all(&header.encode())?;
+    for &offset in &offsets {
+        write.write_all(&(offset as u64).to_le_bytes())?;
+    }
+    for &(_, len) in elems {
+        write.write_all(&(len as u64).to_le_bytes())?;
+    }
+
+    let mut written: usize = 0;
+    for (&offset, &(bytes, _)) in offsets.iter().zip(elems) {
+        // zero the padding
+        write.write_all(&vec![0; offset - written])?;
+        write.write_all(bytes)?;
+        written = offset + bytes.len();
+    }
+    Ok(())
+}
+
+/// Read elements, as their offsets and lengths, and the data they're in.
+#[cfg(feature = "std")]
+fn read_elems<R: Read>(
+    mut read: R,
+    kind: u8,
+    item_size: usize,
+    item_align: usize,
+) -> io::Result<(Vec<usize>, Vec<usize>, Vec<u8>)> {
+    let mut header_bytes = [0; HEADER_LEN];
+    read.read_exact(&mut header_bytes)?;
+    let header = Header::decode(&header_bytes, kind, item_size, item_align)?;
+
+    // read no more than is actually there, in case the header is wrong
+    let read_len = |read: &mut R, len: usize| -> io::Result<Vec<u8>> {
+        let mut buf: Vec<u8> = Vec::new();
+        read.by_ref().take(len as u64).read_to_end(&mut buf)?;
+        match buf.len() == len {
+            true => Ok(buf),
+            false => Err(DecodeError::Truncated.into()),
+        }
+    };
+
+    let tables: Vec<u8> = read_len(&mut read, header.tables_len()?)?;
+    let (offsets, lens) = decode_tables(&tables, &header)?;
+    let data: Vec<u8> = read_len(&mut read, header.data_len)?;
+    Ok((offsets, lens, data))
+}
+
+/// Build a vector around elements which are already packed in `data`.
+#[cfg(feature = "std")]
+fn from_packed<T: ?Sized + Pointee<Metadata = usize>>(
+    data: Vec<u8>,
+    offsets: Vec<usize>,
+    lens: Vec<usize>,
+    item_size: usize,
+    item_align: usize,
+) -> HeteroSizedVec<T> {
+    let mut vec: HeteroSizedVec<T> = HeteroSizedVec::new();
+    // the elements are packed without guard bytes between them
+    vec.guard_len = 0;
+    vec.elems_size = lens.iter().map(|&len| len * item_size).collect();
+    vec.drop_handlers = vec![None; lens.len()];
+    // the offsets are aligned relative to the start of `data`, so copy it
+    // into storage which is aligned for the items
+    vec.storage.realign(item_align);
+    vec.storage.extend_from_slice(&data);
+    vec.mem_indices = offsets;
+    vec.ptr_meta = lens;
+    vec.fill_padding();
+    vec
+}
+
+#[cfg(feature = "std")]
+impl<A: Allocator> HeteroSizedVec<str, A> {
+    /// Write the elements in the format described on `HeteroSizedVecRef`.
+    pub fn write_to<W: Write>(&self, write: W) -> io::Result<()> {
+        let elems: Vec<(&[u8], usize)> = self.iter()
+            .map(|elem| (elem.as_bytes(), elem.len()))
+            .collect();
+        write_elems(write, KIND_STR, 1, 1, &elems)
+    }
+}
+
+#[cfg(feature = "std")]
+impl HeteroSizedVec<str> {
+    /// Read elements written by `write_to`.
+    ///
+    /// The elements are read into storage all at once, rather than being
+    /// pushed one at a time.
+    pub fn read_from<R: Read>(read: R) -> io::Result<Self> {
+        let (offsets, lens, data) = read_elems(read, KIND_STR, 1, 1)?;
+        for (i, (&offset, &len)) in offsets.iter().zip(&lens).enumerate() {
+            str::from_utf8(&data[offset..offset + len])
+                .map_err(|_| DecodeError::InvalidUtf8(i))?;
+        }
+        Ok(from_packed(data, offsets, lens, 1, 1))
+    }
+}
+
+#[cfg(feature = "std")]
+impl<I: HeteroPod, A: Allocator> HeteroSizedVec<[I], A> {
+    /// Write the elements in the format described on `HeteroSizedVecRef`.
+    pub fn write_to<W: Write>(&self, write: W) -> io::Result<()> {
+        let elems: Vec<(&[u8], usize)> = self.iter()
+            .map(|elem| (
+                unsafe {
+                    // plain data has no padding
+                    slice::from_raw_parts(elem.as_ptr() as *const u8, size_of_val(elem))
+                },
+                elem.len(),
+            ))
+            .collect();
+        write_elems(write, KIND_SLICE, size_of::<I>(), align_of::<I>(), &elems)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<I: HeteroPod> HeteroSizedVec<[I]> {
+    /// Read elements written by `write_to`.
+    ///
+    /// The elements are read into storage all at once, rather than being
+    /// pushed one at a time.
+    pub fn read_from<R: Read>(read: R) -> io::Result<Self> {
+        let (offsets, lens, data) = read_elems(
+            read,
+            KIND_SLICE,
+            size_of::<I>(),
+            align_of::<I>(),
+        )?;
+        Ok(from_packed(data, offsets, lens, size_of::<I>(), align_of::<I>()))
+    }
+}
+
+/// Read-only view of serialized elements, which borrows them without copying.
+///
+/// This can wrap any bytes, such as a memory-mapped file, or the result of
+/// `include_bytes!`. They are validated up front, without allocating, and
+/// after that, getting an element takes constant time.
+///
+/// # Format
+///
+/// All integers in the header and tables are little-endian. The items of
+/// slice elements are in the byte order of the target which wrote them,
+/// which is recorded in the header.
+///
+/// | bytes | content                                              |
+/// |-------|------------------------------------------------------|
+/// | 4     | magic bytes, `HSVC`                                  |
+/// | 1     | format version, currently 1                          |
+/// | 1     | byte order, 0 for little-endian and 1 for big-endian |
+/// | 1     | element kind, 0 for `str` and 1 for `[I]`            |
+/// | 1     | zero                                                 |
+/// | 4     | size of `I` as a `u32`, or 1 for `str`               |
+/// | 4     | alignment of `I` as a `u32`, or 1 for `str`          |
+/// | 8     | number of elements, `n`, as a `u64`                  |
+/// | 8     | length of the data, `d`, as a `u64`                  |
+/// | 8 `n` | offset of each element within the data, as a `u64`   |
+/// | 8 `n` | length of each element, in items or bytes, as a `u64`|
+/// | `d`   | data                                                 |
+///
+/// The elements in the data are in order, aligned, and padded with zeroes.
+/// The data starts at a multiple of 8 bytes, so for the elements to be
+/// aligned in memory, the bytes must be aligned like `I`.
+pub struct HeteroSizedVecRef<'a, T: ?Sized> {
+    // the offsets table followed by the lengths table
+    tables: &'a [u8],
+    data: &'a [u8],
+    len: usize,
+
+    p: PhantomData<&'a T>,
+}
+
+impl<'a, T: ?Sized> Clone for HeteroSizedVecRef<'a, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, T: ?Sized> Copy for HeteroSizedVecRef<'a, T> {}
+
+/// Decode and validate serialized bytes, and split them into the tables and
+/// the data.
+fn decode_ref(
+    bytes: &[u8],
+    kind: u8,
+    item_size: usize,
+    item_align: usize,
+) -> Result<(Header, &[u8], &[u8]), DecodeError> {
+    let header = Header::decode(bytes, kind, item_size, item_align)?;
+    let data_start: usize = HEADER_LEN.checked_add(header.tables_len()?)
+        .ok_or(DecodeError::Truncated)?;
+    let data_end: usize = data_start.checked_add(header.data_len)
+        .ok_or(DecodeError::Truncated)?;
+    if bytes.len() < data_end {
+        return Err(DecodeError::Truncated);
+    }
+    let tables: &[u8] = &bytes[HEADER_LEN..data_start];
+    check_elems(tables, &header)?;
+    Ok((header, tables, &bytes[data_start..data_end]))
+}
+
+/// Read an entry of an already-validated table.
+fn table_entry(table: &[u8], index: usize) -> usize {
+    let mut buf = [0; 8];
+    buf.copy_from_slice(&table[index * 8..index * 8 + 8]);
+    u64::from_le_bytes(buf) as usize
+}
+
+impl<'a, T: ?Sized> HeteroSizedVecRef<'a, T> {
+    /// Length in elements.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether there are no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // offset and length of an element
+    fn entry(&self, index: usize) -> Option<(usize, usize)> {
+        if index >= self.len {
+            return None;
+        }
+        Some((
+            table_entry(self.tables, index),
+            table_entry(self.tables, self.len + index),
+        ))
+    }
+
+    pub fn iter<'r>(&'r self) -> RefIter<'r, 'a, T> {
+        RefIter {
+            vec: self,
+            index: 0..self.len(),
+        }
+    }
+}
+
+impl<'a> HeteroSizedVecRef<'a, str> {
+    /// View bytes written by `HeteroSizedVec::<str>::write_to`.
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, DecodeError> {
+        let (header, tables, data) = decode_ref(bytes, KIND_STR, 1, 1)?;
+        for (i, entry) in table_entries(tables, header.count).enumerate() {
+            let (offset, len) = entry?;
+            str::from_utf8(&data[offset..offset + len])
+                .map_err(|_| DecodeError::InvalidUtf8(i))?;
+        }
+        Ok(HeteroSizedVecRef {
+            tables,
+            data,
+            len: header.count,
+
+            p: PhantomData,
+        })
+    }
+
+    /// Get element by index as reference.
+    pub fn get(&self, index: usize) -> Option<&'a str> {
+        let (offset, len) = self.entry(index)?;
+        let bytes: &'a [u8] = &self.data[offset..offset + len];
+        unsafe {
+            // validated in `from_bytes`
+            Some(str::from_utf8_unchecked(bytes))
+        }
+    }
+}
+
+impl<'a, I: HeteroPod> HeteroSizedVecRef<'a, [I]> {
+    /// View bytes written by `HeteroSizedVec::<[I]>::write_to`.
+    ///
+    /// Fails if the elements are not aligned in memory, which the bytes must
+    /// be aligned enough for.
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, DecodeError> {
+        let (header, tables, data) = decode_ref(
+            bytes,
+            KIND_SLICE,
+            size_of::<I>(),
+            align_of::<I>(),
+        )?;
+        // the offsets are multiples of the alignment, so only the data needs
+        // to be checked
+        if header.count > 0 && !data.as_ptr().addr().is_multiple_of(align_of::<I>()) {
+            return Err(DecodeError::Misaligned(0));
+        }
+        Ok(HeteroSizedVecRef {
+            tables,
+            data,
+            len: header.count,
+
+            p: PhantomData,
+        })
+    }
+
+    /// Get element by index as reference.
+    pub fn get(&self, index: usize) -> Option<&'a [I]> {
+        let (offset, len) = self.entry(index)?;
+        unsafe {
+            // bounds and alignment were checked in `from_bytes`
+            Some(&*slice_from_raw_parts(
+                self.data.as_ptr().add(offset) as *const I,
+                len,
+            ))
+        }
+    }
+}
+
+// index operator
+
+impl<'a> Index<usize> for HeteroSizedVecRef<'a, str> {
+    type Output = str;
+
+    fn index(&self, index: usize) -> &str {
+        self.get(index).unwrap()
+    }
+}
+
+impl<'a, I: HeteroPod> Index<usize> for HeteroSizedVecRef<'a, [I]> {
+    type Output = [I];
+
+    fn index(&self, index: usize) -> &[I] {
+        self.get(index).unwrap()
+    }
+}
+
+// iterators
+
+pub struct RefIter<'r, 'a, T: ?Sized> {
+    vec: &'r HeteroSizedVecRef<'a, T>,
+    index: Range<usize>,
+}
+
+impl<'r, 'a> Iterator for RefIter<'r, 'a, str> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let vec = self.vec;
+        self.index.next()
+            .map(|i| vec.get(i).unwrap())
+    }
+}
+
+impl<'r, 'a, I: HeteroPod> Iterator for RefIter<'r, 'a, [I]> {
+    type Item = &'a [I];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let vec = self.vec;
+        self.index.next()
+            .map(|i| vec.get(i).unwrap())
+    }
+}
@@ -0,0 +1,225 @@
+
+use crate::{
+    HeteroSizedVec,
+    pushable::{
+        HeteroSizedPush,
+        InPlace,
+    },
+};
+
+use core::{
+    ops::{
+        Index,
+        IndexMut,
+    },
+    marker::Unsize,
+};
+use alloc::vec::Vec;
+
+/// Key to an element of a `HeteroSizedSlotMap`.
+///
+/// A handle stays valid until its element is removed, after which it will
+/// never refer to an element again, even if its slot is reused. A slot is
+/// reused for at most 2^32 elements, after which it's retired, so that its
+/// generation never wraps around.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Handle {
+    slot: usize,
+    generation: u32,
+}
+
+/// Collection of an unsized type, accessed by stable handles.
+///
+/// The elements are still stored densely, like in a `HeteroSizedVec`, but
+/// removing an element does not invalidate the handles to other elements.
+pub struct HeteroSizedSlotMap<T: ?Sized> {
+    // densely stored elements, in no particular order
+    elems: HeteroSizedVec<T>,
+    // slot index of each element
+    elem_slots: Vec<usize>,
+    pub(crate) slots: Vec<Slot>,
+    // head of the linked list of free slots
+    free_head: Option<usize>,
+}
+
+pub(crate) struct Slot {
+    // incremented each time the slot is vacated
+    pub(crate) generation: u32,
+    state: SlotState,
+}
+
+enum SlotState {
+    // index of the element in `elems`
+    Occupied(usize),
+    // next slot in the free list
+    Free(Option<usize>),
+    // never reused, as its generation is used up
+    Retired,
+}
+
+impl<T: ?Sized> HeteroSizedSlotMap<T> {
+    /// New, empty slot map.
+    pub fn new() -> Self {
+        HeteroSizedSlotMap {
+            elems: HeteroSizedVec::new(),
+            elem_slots: Vec::new(),
+            slots: Vec::new(),
+            free_head: None,
+        }
+    }
+
+    /// Length in elements.
+    pub fn len(&self) -> usize {
+        self.elems.len()
+    }
+
+    /// Whether there are no elements.
+    pub fn is_empty(&self) -> bool {
+        self.elems.is_empty()
+    }
+
+    /// Push an element, and get its handle.
+    pub fn push<E: HeteroSizedPush<T>>(&mut self, elem: E) -> Handle {
+        let index: usize = self.elems.len();
+        self.elems.push(elem);
+
+        let slot: usize = match self.free_head {
+            Some(slot) => {
+                self.free_head = match self.slots[slot].state {
+                    SlotState::Free(next) => next,
+                    SlotState::Occupied(_) | SlotState::Retired => unreachable!(),
+                };
+                self.slots[slot].state = SlotState::Occupied(index);
+                slot
+            },
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    state: SlotState::Occupied(index),
+                });
+                self.slots.len() - 1
+            },
+        };
+        self.elem_slots.push(slot);
+
+        Handle {
+            slot,
+            generation: self.slots[slot].generation,
+        }
+    }
+
+    /// Push some value which unsizes to the element type, and get its
+    /// handle.
+    pub fn push_value<E: Unsize<T>>(&mut self, elem: E) -> Handle {
+        self.push(InPlace(elem))
+    }
+
+    // index into `elems` of the handle's element, if it is still present
+    fn elem_index(&self, handle: Handle) -> Option<usize> {
+        match self.slots.get(handle.slot) {
+            Some(&Slot {
+                generation,
+                state: SlotState::Occupied(index),
+            }) if generation == handle.generation => Some(index),
+            _ => None,
+        }
+    }
+
+    /// Whether the handle's element is still present.
+    pub fn contains(&self, handle: Handle) -> bool {
+        self.elem_index(handle).is_some()
+    }
+
+    /// Get element by handle as reference.
+    pub fn get(&self, handle: Handle) -> Option<&T> {
+        self.elems.get(self.elem_index(handle)?)
+    }
+
+    /// Get element by handle as mutable reference.
+    pub fn get_mut(&mut self, handle: Handle) -> Option<&mut T> {
+        let index: usize = self.elem_index(handle)?;
+        self.elems.get_mut(index)
+    }
+
+    /// Remove and drop an element by handle.
+    ///
+    /// Returns whether the element was present.
+    pub fn remove(&mut self, handle: Handle) -> bool {
+        let index: usize = match self.elem_index(handle) {
+            Some(index) => index,
+            None => return false,
+        };
+
+        // vacate the slot, or retire it if its generation would wrap, since
+        // then a stale handle would match it again
+        let slot = &mut self.slots[handle.slot];
+        match slot.generation.checked_add(1) {
+            Some(generation) => {
+                slot.generation = generation;
+                slot.state = SlotState::Free(self.free_head);
+                self.free_head = Some(handle.slot);
+            },
+            None => slot.state = SlotState::Retired,
+        }
+
+        // the last element is moved into the removed element's index
+        self.elem_slots.swap_remove(index);
+        if let Some(&moved_slot) = self.elem_slots.get(index) {
+            self.slots[moved_slot].state = SlotState::Occupied(index);
+        }
+        self.elems.swap_remove_drop(index);
+
+        true
+    }
+
+    /// Iterate over the elements and their handles, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item=(Handle, &T)> {
+        let slots = &self.slots;
+        self.elem_slots.iter()
+            .zip(self.elems.iter())
+            .map(move |(&slot, elem)| (
+                Handle {
+                    slot,
+                    generation: slots[slot].generation,
+                },
+                elem,
+            ))
+    }
+
+    /// Iterate mutably over the elements and their handles, in no particular
+    /// order.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item=(Handle, &mut T)> {
+        let slots = &self.slots;
+        self.elem_slots.iter()
+            .zip(self.elems.iter_mut())
+            .map(move |(&slot, elem)| (
+                Handle {
+                    slot,
+                    generation: slots[slot].generation,
+                },
+                elem,
+            ))
+    }
+}
+
+impl<T: ?Sized> Default for HeteroSizedSlotMap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// index operator
+
+impl<T: ?Sized> Index<Handle> for HeteroSizedSlotMap<T> {
+    type Output = T;
+
+    fn index(&self, handle: Handle) -> &T {
+        self.get(handle).unwrap()
+    }
+}
+
+impl<T: ?Sized> IndexMut<Handle> for HeteroSizedSlotMap<T> {
+    fn index_mut(&mut self, handle: Handle) -> &mut T {
+        self.get_mut(handle).unwrap()
+    }
+}
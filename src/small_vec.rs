@@ -0,0 +1,261 @@
+
+use crate::{
+    HeteroSizedVec,
+    HeteroSizedArrayVec,
+    pushable::{
+        HeteroSizedPush,
+        InPlace,
+    },
+    memory::DropHandler,
+};
+
+use core::{
+    ops::{
+        Index,
+        IndexMut,
+        Range,
+    },
+    mem::{
+        size_of_val,
+        align_of_val,
+    },
+    marker::Unsize,
+    fmt::{
+        self,
+        Debug,
+        Formatter,
+    },
+};
+
+/// Vector of an unsized type, which stores its elements inline until they
+/// exceed its inline capacity, and then moves them to the heap.
+///
+/// The inline capacity is `BYTES` bytes and `N` elements, as in a
+/// `HeteroSizedArrayVec`.
+pub struct HeteroSizedSmallVec<T: ?Sized, const BYTES: usize, const N: usize> {
+    repr: Repr<T, BYTES, N>,
+}
+
+enum Repr<T: ?Sized, const BYTES: usize, const N: usize> {
+    Inline(HeteroSizedArrayVec<T, BYTES, N>),
+    Heap(HeteroSizedVec<T>),
+}
+
+// an element which has already been moved out of an inline vector, pushed
+// onto a heap vector
+struct Spilled<T: ?Sized> {
+    ptr: *mut T,
+    drop_handler: Option<DropHandler<T>>,
+}
+
+unsafe impl<T: ?Sized> HeteroSizedPush<T> for Spilled<T> {
+    unsafe fn elem_size(&self) -> usize {
+        size_of_val(&*self.ptr)
+    }
+
+    unsafe fn elem_align(&self) -> usize {
+        align_of_val(&*self.ptr)
+    }
+
+    unsafe fn elem_ptr(&self) -> *const T {
+        self.ptr
+    }
+
+    unsafe fn elem_drop_handler(&self) -> Option<DropHandler<T>> {
+        self.drop_handler
+    }
+
+    // the concrete type is lost by now, but the heap vector doesn't track
+    // types anyways, so `elem_type` is left as the default
+
+    unsafe fn outer_drop(&mut self) {}
+}
+
+impl<T: ?Sized, const BYTES: usize, const N: usize> HeteroSizedSmallVec<T, BYTES, N> {
+    /// New, empty vector.
+    pub fn new() -> Self {
+        HeteroSizedSmallVec {
+            repr: Repr::Inline(HeteroSizedArrayVec::new()),
+        }
+    }
+
+    /// Length in elements.
+    pub fn len(&self) -> usize {
+        match self.repr {
+            Repr::Inline(ref vec) => vec.len(),
+            Repr::Heap(ref vec) => vec.len(),
+        }
+    }
+
+    /// Whether there are no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether the elements are still stored inline.
+    pub fn is_inline(&self) -> bool {
+        match self.repr {
+            Repr::Inline(_) => true,
+            Repr::Heap(_) => false,
+        }
+    }
+
+    /// Push an element onto the vector, moving all elements to the heap if
+    /// it doesn't fit inline.
+    pub fn push<E: HeteroSizedPush<T>>(&mut self, elem: E) {
+        let elem = match self.repr {
+            Repr::Inline(ref mut inline) => match inline.try_push(elem) {
+                Ok(()) => return,
+                Err(elem) => elem,
+            },
+            Repr::Heap(ref mut heap) => return heap.push(elem),
+        };
+
+        self.spill();
+        match self.repr {
+            Repr::Heap(ref mut heap) => heap.push(elem),
+            Repr::Inline(_) => unreachable!(),
+        }
+    }
+
+    /// Push some value which unsizes to the element type.
+    pub fn push_value<E: Unsize<T>>(&mut self, elem: E) {
+        self.push(InPlace(elem));
+    }
+
+    // move the elements from inline storage to the heap
+    fn spill(&mut self) {
+        let inline = match self.repr {
+            Repr::Inline(ref mut inline) => inline,
+            Repr::Heap(_) => return,
+        };
+
+        let mut heap: HeteroSizedVec<T> = HeteroSizedVec::new();
+        unsafe {
+            // forget first, so that a panic causes a leak rather than a
+            // double-free
+            let len: usize = inline.forget_all();
+            for i in 0..len {
+                let (ptr, drop_handler) = inline.raw_elem(i);
+                heap.push(Spilled { ptr, drop_handler });
+            }
+        }
+        self.repr = Repr::Heap(heap);
+    }
+
+    /// Get element by index as reference.
+    pub fn get(&self, index: usize) -> Option<&T> {
+        match self.repr {
+            Repr::Inline(ref vec) => vec.get(index),
+            Repr::Heap(ref vec) => vec.get(index),
+        }
+    }
+
+    /// Get element by index as mutable reference.
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        match self.repr {
+            Repr::Inline(ref mut vec) => vec.get_mut(index),
+            Repr::Heap(ref mut vec) => vec.get_mut(index),
+        }
+    }
+
+    pub fn iter(&self) -> SmallVecIter<'_, T, BYTES, N> {
+        SmallVecIter {
+            vec: self,
+            index: 0..self.len(),
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> SmallVecIterMut<'_, T, BYTES, N> {
+        SmallVecIterMut {
+            index: 0..self.len(),
+            vec: self,
+        }
+    }
+}
+
+impl<T: ?Sized, const BYTES: usize, const N: usize> Default for HeteroSizedSmallVec<T, BYTES, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// index operator
+
+impl<T: ?Sized, const BYTES: usize, const N: usize> Index<usize> for HeteroSizedSmallVec<T, BYTES, N> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        self.get(index).unwrap()
+    }
+}
+
+impl<T: ?Sized, const BYTES: usize, const N: usize> IndexMut<usize> for HeteroSizedSmallVec<T, BYTES, N> {
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        self.get_mut(index).unwrap()
+    }
+}
+
+// iterators
+
+pub struct SmallVecIter<'a, T: ?Sized, const BYTES: usize, const N: usize> {
+    vec: &'a HeteroSizedSmallVec<T, BYTES, N>,
+    index: Range<usize>,
+}
+
+impl<'a, T: ?Sized, const BYTES: usize, const N: usize> Iterator for SmallVecIter<'a, T, BYTES, N> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.index.next()
+            .map(|i| &self.vec[i])
+    }
+}
+
+pub struct SmallVecIterMut<'a, T: ?Sized, const BYTES: usize, const N: usize> {
+    vec: &'a mut HeteroSizedSmallVec<T, BYTES, N>,
+    index: Range<usize>,
+}
+
+impl<'a, T: ?Sized, const BYTES: usize, const N: usize> Iterator for SmallVecIterMut<'a, T, BYTES, N> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.index.next()
+            .map(|i| unsafe {
+                // each index is visited once
+                &mut *(&mut self.vec[i] as *mut T)
+            })
+    }
+}
+
+impl<'a, T: ?Sized, const BYTES: usize, const N: usize> IntoIterator for &'a HeteroSizedSmallVec<T, BYTES, N> {
+    type Item = &'a T;
+    type IntoIter = SmallVecIter<'a, T, BYTES, N>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, T: ?Sized, const BYTES: usize, const N: usize> IntoIterator for &'a mut HeteroSizedSmallVec<T, BYTES, N> {
+    type Item = &'a mut T;
+    type IntoIter = SmallVecIterMut<'a, T, BYTES, N>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+// debug
+
+impl<T: ?Sized, const BYTES: usize, const N: usize> Debug for HeteroSizedSmallVec<T, BYTES, N>
+where
+    for<'a> &'a T: Debug
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_list()
+            .entries(self)
+            .finish()
+    }
+}
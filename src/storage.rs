@@ -0,0 +1,208 @@
+
+use core::{
+    alloc::{
+        Allocator,
+        AllocError,
+        Layout,
+    },
+    ops::{
+        Deref,
+        DerefMut,
+    },
+    ptr::{
+        self,
+        NonNull,
+    },
+    slice,
+    cmp::max,
+};
+use alloc::alloc::handle_alloc_error;
+
+/// Growable byte buffer which is aligned enough for the elements in it.
+///
+/// This has the parts of the `Vec<u8>` interface which element storage
+/// needs, but unlike a `Vec<u8>`, its allocation can be aligned to more
+/// than 1.
+pub(crate) struct Storage<A: Allocator> {
+    ptr: NonNull<u8>,
+    len: usize,
+    cap: usize,
+    // alignment of the allocation, which only ever increases
+    align: usize,
+    alloc: A,
+}
+
+unsafe impl<A: Allocator + Send> Send for Storage<A> {}
+
+unsafe impl<A: Allocator + Sync> Sync for Storage<A> {}
+
+/// Dangling pointer aligned to `align`, without provenance.
+fn dangling(align: usize) -> NonNull<u8> {
+    NonNull::new(ptr::without_provenance_mut(align)).unwrap()
+}
+
+impl<A: Allocator> Storage<A> {
+    /// New, empty buffer, which does not allocate.
+    pub(crate) fn new_in(alloc: A) -> Self {
+        Storage {
+            ptr: dangling(1),
+            len: 0,
+            cap: 0,
+            align: 1,
+            alloc,
+        }
+    }
+
+    /// New, empty buffer with space for `cap` bytes aligned to `align`.
+    pub(crate) fn with_capacity_in(cap: usize, align: usize, alloc: A) -> Self {
+        let mut storage = Storage::new_in(alloc);
+        storage.realloc(cap, align);
+        storage
+    }
+
+    pub(crate) fn allocator(&self) -> &A {
+        &self.alloc
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    pub(crate) fn capacity(&self) -> usize {
+        self.cap
+    }
+
+    pub(crate) fn align(&self) -> usize {
+        self.align
+    }
+
+    pub(crate) fn as_ptr(&self) -> *const u8 {
+        self.ptr.as_ptr()
+    }
+
+    pub(crate) fn as_mut_ptr(&mut self) -> *mut u8 {
+        self.ptr.as_ptr()
+    }
+
+    /// Set the length, without initializing anything.
+    pub(crate) unsafe fn set_len(&mut self, len: usize) {
+        debug_assert!(len <= self.cap);
+        self.len = len;
+    }
+
+    // move the contents into a new allocation
+    fn realloc(&mut self, cap: usize, align: usize) {
+        let new_ptr: NonNull<u8> = match cap {
+            0 => dangling(align),
+            _ => {
+                let layout = Layout::from_size_align(cap, align).unwrap();
+                match self.alloc.allocate(layout) {
+                    Ok(new_ptr) => new_ptr.cast::<u8>(),
+                    Err(AllocError) => handle_alloc_error(layout),
+                }
+            },
+        };
+        unsafe {
+            ptr::copy_nonoverlapping::<u8>(
+                // src:
+                self.ptr.as_ptr(),
+                // dst:
+                new_ptr.as_ptr(),
+                // len:
+                self.len,
+            );
+            self.dealloc();
+        }
+        self.ptr = new_ptr;
+        self.cap = cap;
+        self.align = align;
+    }
+
+    unsafe fn dealloc(&mut self) {
+        if self.cap > 0 {
+            self.alloc.deallocate(
+                self.ptr,
+                Layout::from_size_align_unchecked(self.cap, self.align),
+            );
+        }
+    }
+
+    /// Make sure that the allocation is aligned to at least `align`.
+    ///
+    /// Offsets stay aligned, since the old alignment divides the new one.
+    pub(crate) fn realign(&mut self, align: usize) {
+        if align > self.align {
+            self.realloc(self.cap, align);
+        }
+    }
+
+    /// Make sure there's space for `additional` more bytes.
+    pub(crate) fn reserve(&mut self, additional: usize) {
+        let needed: usize = self.len.checked_add(additional)
+            .expect("capacity overflow");
+        if needed > self.cap {
+            self.realloc(max(max(self.cap * 2, needed), 8), self.align);
+        }
+    }
+
+    /// Grow to `len` bytes by filling with `byte`, or else shrink to it.
+    pub(crate) fn resize(&mut self, len: usize, byte: u8) {
+        if len > self.len {
+            self.reserve(len - self.len);
+            unsafe {
+                ptr::write_bytes(self.ptr.as_ptr().add(self.len), byte, len - self.len);
+            }
+        }
+        self.len = len;
+    }
+
+    /// Shrink to `len` bytes, if longer.
+    pub(crate) fn truncate(&mut self, len: usize) {
+        if len < self.len {
+            self.len = len;
+        }
+    }
+
+    /// Append a copy of `bytes`.
+    #[cfg(feature = "std")]
+    pub(crate) fn extend_from_slice(&mut self, bytes: &[u8]) {
+        self.reserve(bytes.len());
+        unsafe {
+            ptr::copy_nonoverlapping::<u8>(
+                // src:
+                bytes.as_ptr(),
+                // dst:
+                self.ptr.as_ptr().add(self.len),
+                // len:
+                bytes.len(),
+            );
+        }
+        self.len += bytes.len();
+    }
+}
+
+impl<A: Allocator> Deref for Storage<A> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe {
+            slice::from_raw_parts(self.ptr.as_ptr(), self.len)
+        }
+    }
+}
+
+impl<A: Allocator> DerefMut for Storage<A> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe {
+            slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len)
+        }
+    }
+}
+
+impl<A: Allocator> Drop for Storage<A> {
+    fn drop(&mut self) {
+        unsafe {
+            self.dealloc();
+        }
+    }
+}
@@ -1,6 +1,15 @@
 
 use crate::HeteroSizedVec;
 
+// not in the prelude of a `no_std` crate
+#[cfg(not(feature = "std"))]
+use std::{
+    vec::Vec,
+    string::String,
+    boxed::Box,
+    borrow::ToOwned,
+};
+
 #[test]
 fn closure_basic() {
     #[inline(never)]
@@ -213,8 +222,13 @@ fn index_out_of_bounds() {
 }
 
 pub mod drop_test {
+    #[cfg(not(feature = "std"))]
+    use std::vec::Vec;
+
     #[test]
     #[should_panic]
+    // this deliberately leaks, which miri reports
+    #[cfg_attr(miri, ignore)]
     fn dangling_pointer_should_panic() {
         use std::mem::forget;
 
@@ -260,12 +274,15 @@ pub mod drop_test {
     };
 
     pub use std::mem::drop;
+    use std::mem::ManuallyDrop;
 
-    #[derive(Clone)]
+    #[derive(Clone, Default)]
     pub struct DropTestCounter { alive_count: Arc<AtomicI64> }
 
     pub struct DropTestToken {
-        alive_count: Arc<AtomicI64>,
+        // only released by the first drop, so that a double-drop panics
+        // rather than freeing the counter twice
+        alive_count: ManuallyDrop<Arc<AtomicI64>>,
         already_dropped: bool,
     }
 
@@ -279,7 +296,7 @@ pub mod drop_test {
         pub fn token(&self) -> DropTestToken {
             self.alive_count.fetch_add(1, Ordering::Relaxed);
             DropTestToken {
-                alive_count: self.alive_count.clone(),
+                alive_count: ManuallyDrop::new(self.alive_count.clone()),
                 already_dropped: false,
             }
         }
@@ -301,6 +318,9 @@ pub mod drop_test {
 
             self.already_dropped = true;
             self.alive_count.fetch_sub(1, Ordering::Relaxed);
+            unsafe {
+                ManuallyDrop::drop(&mut self.alive_count);
+            }
         }
     }
 }
@@ -312,7 +332,7 @@ fn closure_drop_test() {
     #[inline(never)]
     fn closure(n: usize, token: DropTestToken) -> impl Fn() -> usize {
         move || {
-            let token = &token;
+            let _token = &token;
             n
         }
     }
@@ -364,7 +384,7 @@ fn test_alignment() {
     macro_rules! align_impl {
         ($align:expr)=>{{
             #[repr(align($align))]
-            struct SpecialAlign(u8);
+            struct SpecialAlign(#[allow(dead_code)] u8);
 
             impl Align for SpecialAlign {
                 fn addr(&self) -> usize {
@@ -394,4 +414,2093 @@ fn test_alignment() {
         assert!(elem.addr() % elem.align() == 0);
     }
 
-}
\ No newline at end of file
+}
+
+#[test]
+fn last_resize_rows() {
+    let mut vec: HeteroSizedVec<[u32]> = HeteroSizedVec::new();
+
+    vec.push_value([0_u32; 0]);
+    vec.last_push_item(1);
+    vec.last_extend_from_slice(&[2, 3]);
+
+    vec.push_value([4_u32]);
+    vec.last_extend_from_slice(&[5, 6, 7, 8]);
+    vec.last_truncate(3);
+
+    vec.push(&[9_u32] as &[_]);
+    vec.last_push_item(10);
+
+    assert_eq!(vec.len(), 3);
+    assert_eq!(&vec[0], &[1, 2, 3]);
+    assert_eq!(&vec[1], &[4, 5, 6]);
+    assert_eq!(&vec[2], &[9, 10]);
+}
+
+#[test]
+fn last_resize_str() {
+    let mut vec: HeteroSizedVec<str> = HeteroSizedVec::new();
+
+    vec.push("hello");
+    vec.last_extend_from_slice(", world");
+    vec.push("");
+    vec.last_push_item('ö');
+    vec.last_extend_from_slice("foo");
+    vec.last_truncate(2);
+
+    assert_eq!(&vec[0], "hello, world");
+    assert_eq!(&vec[1], "ö");
+}
+
+#[test]
+#[should_panic]
+fn last_truncate_str_char_boundary() {
+    let mut vec: HeteroSizedVec<str> = HeteroSizedVec::new();
+
+    vec.push("ö");
+    vec.last_truncate(1);
+}
+
+#[test]
+fn last_resize_drop_test() {
+    use drop_test::*;
+
+    let counter = DropTestCounter::new();
+
+    let mut vec: HeteroSizedVec<[DropTestToken]> = HeteroSizedVec::new();
+
+    vec.push_value([counter.token(), counter.token()]);
+    for _ in 0..10 {
+        vec.last_push_item(counter.token());
+    }
+    vec.last_truncate(5);
+    assert_eq!(vec[0].len(), 5);
+
+    vec.push(Vec::new());
+    for _ in 0..10 {
+        vec.last_push_item(counter.token());
+    }
+    vec.last_truncate(0);
+    assert_eq!(vec[1].len(), 0);
+
+    drop(vec);
+    counter.check();
+}
+
+#[test]
+fn any_downcast() {
+    use std::any::Any;
+
+    let mut vec: HeteroSizedVec<dyn Any> = HeteroSizedVec::new();
+
+    vec.push_value(1_u32);
+    vec.push_value(String::from("hello"));
+    vec.push_value(2_u32);
+    vec.push_value(3_u64);
+    vec.push_value(4_u32);
+
+    assert_eq!(vec.get_as::<u32>(0), Some(&1));
+    assert_eq!(vec.get_as::<u64>(0), None);
+    assert_eq!(vec.get_as::<u32>(5), None);
+
+    *vec.get_mut_as::<u32>(2).unwrap() += 10;
+
+    let u32s: Vec<u32> = vec.iter_of::<u32>().copied().collect();
+    assert_eq!(u32s, vec![1, 12, 4]);
+
+    assert_eq!(vec.remove_as::<u32>(1), None);
+    assert_eq!(vec.remove_as::<String>(1), Some(String::from("hello")));
+    assert_eq!(vec.remove_as::<u32>(3), Some(4));
+    assert_eq!(vec.len(), 3);
+    assert_eq!(vec.get_as::<u64>(2), Some(&3));
+}
+
+#[test]
+fn any_remove_drop_test() {
+    use std::any::Any;
+    use drop_test::*;
+
+    let counter = DropTestCounter::new();
+
+    let mut vec: HeteroSizedVec<dyn Any + Send> = HeteroSizedVec::new();
+    for _ in 0..10 {
+        vec.push_value(counter.token());
+        vec.push_value(0_u8);
+    }
+
+    let mut removed = Vec::new();
+    while let Some(token) = vec.remove_as::<DropTestToken>(0) {
+        removed.push(token);
+        vec.remove_as::<u8>(0).unwrap();
+    }
+    assert_eq!(vec.len(), 0);
+    assert_eq!(removed.len(), 10);
+
+    drop(removed);
+    drop(vec);
+    counter.check();
+}
+
+#[test]
+fn type_tracking() {
+    use std::any::TypeId;
+    use std::fmt::Debug;
+
+    let mut vec: HeteroSizedVec<dyn Debug> =
+        HeteroSizedVec::with_type_tracking();
+    assert!(vec.tracks_types());
+
+    vec.push_value(1_u32);
+    vec.push_value("hello");
+    vec.push_value(2_u32);
+    vec.push_value([3_u8; 4]);
+
+    assert_eq!(vec.type_id_of(0), Some(TypeId::of::<u32>()));
+    assert_eq!(vec.type_id_of(1), Some(TypeId::of::<&str>()));
+    assert_eq!(vec.type_name_of(3), Some("[u8; 4]"));
+    assert_eq!(vec.type_id_of(4), None);
+
+    let groups: Vec<(TypeId, Vec<usize>)> = vec.group_by_type().unwrap()
+        .into_iter()
+        .map(|(ty, indices)| (ty.id(), indices))
+        .collect();
+    assert_eq!(groups, vec![
+        (TypeId::of::<u32>(), vec![0, 2]),
+        (TypeId::of::<&str>(), vec![1]),
+        (TypeId::of::<[u8; 4]>(), vec![3]),
+    ]);
+
+    assert_eq!(
+        format!("{:?}", vec.debug_types()),
+        "[u32, &str, u32, [u8; 4]]",
+    );
+
+    let untracked: HeteroSizedVec<dyn Debug> = HeteroSizedVec::new();
+    assert!(!untracked.tracks_types());
+    assert!(untracked.group_by_type().is_none());
+}
+
+#[test]
+fn type_tracking_remove_and_resize() {
+    use std::any::{Any, TypeId};
+
+    let mut vec: HeteroSizedVec<dyn Any> =
+        HeteroSizedVec::with_type_tracking();
+    vec.push_value(1_u8);
+    vec.push_value(2_u16);
+    vec.push_value(3_u32);
+
+    vec.remove_as::<u16>(1).unwrap();
+    assert_eq!(vec.type_id_of(0), Some(TypeId::of::<u8>()));
+    assert_eq!(vec.type_id_of(1), Some(TypeId::of::<u32>()));
+    assert_eq!(vec.type_id_of(2), None);
+
+    let mut arrays: HeteroSizedVec<[u8]> =
+        HeteroSizedVec::with_type_tracking();
+    arrays.push_value([1_u8, 2]);
+    assert_eq!(arrays.type_id_of(0), Some(TypeId::of::<[u8; 2]>()));
+    arrays.last_push_item(3);
+    assert_eq!(arrays.type_id_of(0), Some(TypeId::of::<[u8]>()));
+}
+
+#[test]
+fn type_tracking_default_elem_type() {
+    use crate::HeteroSizedPush;
+    use std::any::TypeId;
+
+    // an implementation which doesn't say what its concrete type is
+    struct Bytes<'a>(&'a [u8]);
+
+    unsafe impl<'a> HeteroSizedPush<[u8]> for Bytes<'a> {
+        unsafe fn elem_size(&self) -> usize { self.0.len() }
+
+        unsafe fn elem_align(&self) -> usize { 1 }
+
+        unsafe fn elem_ptr(&self) -> *const [u8] { self.0 }
+
+        unsafe fn elem_drop_handler(&self) -> Option<fn(*mut u8, usize)> { None }
+
+        unsafe fn outer_drop(&mut self) {}
+    }
+
+    let mut arrays: HeteroSizedVec<[u8]> =
+        HeteroSizedVec::with_type_tracking();
+    arrays.push(Bytes(&[1, 2, 3]));
+    assert_eq!(&arrays[0], &[1, 2, 3]);
+    assert_eq!(arrays.type_id_of(0), Some(TypeId::of::<[u8]>()));
+}
+
+#[test]
+fn group_by_type_iteration() {
+    use crate::integrity::GUARD_LEN;
+
+    pub trait Particle {
+        fn step(&mut self);
+
+        fn pos(&self) -> f32;
+    }
+
+    struct Slow(f32);
+
+    impl Particle for Slow {
+        fn step(&mut self) { self.0 += 1.0; }
+
+        fn pos(&self) -> f32 { self.0 }
+    }
+
+    struct Fast(f64);
+
+    impl Particle for Fast {
+        fn step(&mut self) { self.0 += 10.0; }
+
+        fn pos(&self) -> f32 { self.0 as f32 }
+    }
+
+    let mut vec: HeteroSizedVec<dyn Particle> =
+        HeteroSizedVec::with_type_tracking();
+    vec.push_value(Slow(0.0));
+    vec.push_value(Slow(1.0));
+    vec.push_value(Fast(0.0));
+    vec.push_value(Slow(2.0));
+    vec.push_value(Fast(1.0));
+    vec.push_value(Fast(2.0));
+
+    // guard bytes between elements break up every run
+    let expect_runs = |lens: Vec<usize>| match GUARD_LEN {
+        0 => lens,
+        _ => vec![1; lens.iter().sum()],
+    };
+
+    let mut run_lens = Vec::new();
+    vec.for_each_run_of::<Slow, _>(|run| {
+        run_lens.push(run.len());
+        for particle in run {
+            particle.0 += 1.0;
+        }
+    });
+    assert_eq!(run_lens, expect_runs(vec![2, 1]));
+
+    let mut run_lens = Vec::new();
+    vec.for_each_run_of::<Fast, _>(|run| run_lens.push(run.len()));
+    assert_eq!(run_lens, expect_runs(vec![1, 2]));
+
+    vec.for_each_of::<Fast, _>(Particle::step);
+
+    let positions: Vec<f32> = vec.iter().map(|p| p.pos()).collect();
+    assert_eq!(positions, vec![1.0, 2.0, 10.0, 3.0, 11.0, 12.0]);
+}
+
+#[test]
+#[should_panic]
+fn group_by_type_untracked() {
+    let mut vec: HeteroSizedVec<dyn Fn() -> usize> = HeteroSizedVec::new();
+    vec.push_value(|| 1);
+    vec.for_each_of::<u32, _>(|_| ());
+}
+
+#[test]
+fn buckets_basic() {
+    use crate::HeteroSizedBuckets;
+    use std::fmt::Debug;
+
+    let mut buckets: HeteroSizedBuckets<dyn Debug> = HeteroSizedBuckets::new();
+    buckets.push_value(1_u32);
+    buckets.push_value("a");
+    buckets.push_value(2_u32);
+    buckets.push_value(3_u64);
+    buckets.push_value("b");
+    buckets.push_value(4_u32);
+
+    assert_eq!(buckets.len(), 6);
+    assert_eq!(buckets.types().len(), 3);
+
+    let debugs: Vec<String> = buckets.iter()
+        .map(|elem| format!("{:?}", elem))
+        .collect();
+    assert_eq!(debugs, vec!["1", "\"a\"", "2", "3", "\"b\"", "4"]);
+
+    for n in buckets.slice_of_mut::<u32>() {
+        *n *= 10;
+    }
+    assert_eq!(buckets.slice_of::<u32>(), &[10, 20, 40]);
+    assert_eq!(buckets.slice_of::<&str>(), &["a", "b"]);
+    assert_eq!(buckets.slice_of::<u64>(), &[3]);
+    assert_eq!(buckets.slice_of::<u8>(), &[] as &[u8]);
+
+    assert_eq!(format!("{:?}", &buckets[2]), "20");
+    assert_eq!(format!("{:?}", &buckets[5]), "40");
+}
+
+#[test]
+fn buckets_drop_test() {
+    use crate::HeteroSizedBuckets;
+    use drop_test::*;
+
+    let counter = DropTestCounter::new();
+
+    let mut buckets: HeteroSizedBuckets<dyn Send> = HeteroSizedBuckets::new();
+    for i in 0..20 {
+        buckets.push_value(counter.token());
+        if i % 3 == 0 {
+            buckets.push_value((counter.token(), counter.token()));
+        }
+    }
+    assert_eq!(buckets.slice_of::<DropTestToken>().len(), 20);
+    assert_eq!(buckets.slice_of::<(DropTestToken, DropTestToken)>().len(), 7);
+
+    drop(buckets);
+    counter.check();
+}
+
+#[test]
+fn slot_map_handles() {
+    use crate::{HeteroSizedSlotMap, Handle};
+
+    let mut map: HeteroSizedSlotMap<str> = HeteroSizedSlotMap::new();
+
+    let a = map.push("a");
+    let b = map.push("bb");
+    let c = map.push("ccc");
+
+    assert!(map.remove(a));
+    assert!(!map.remove(a));
+    assert!(!map.contains(a));
+    assert_eq!(map.get(a), None);
+    assert_eq!(&map[b], "bb");
+    assert_eq!(&map[c], "ccc");
+
+    // reuses the slot of `a`, but not its handle
+    let d = map.push("dddd");
+    assert_ne!(a, d);
+    assert_eq!(map.get(a), None);
+    assert_eq!(&map[d], "dddd");
+    assert_eq!(map.len(), 3);
+
+    assert!(map.remove(c));
+    assert_eq!(&map[b], "bb");
+    assert_eq!(&map[d], "dddd");
+
+    let mut elems: Vec<(Handle, String)> = map.iter()
+        .map(|(handle, elem)| (handle, elem.to_owned()))
+        .collect();
+    elems.sort_by_key(|(_, elem)| elem.len());
+    assert_eq!(elems, vec![
+        (b, String::from("bb")),
+        (d, String::from("dddd")),
+    ]);
+}
+
+#[test]
+fn slot_map_generation_exhausted() {
+    use crate::HeteroSizedSlotMap;
+
+    let mut map: HeteroSizedSlotMap<str> = HeteroSizedSlotMap::new();
+    let a = map.push("a");
+    assert!(map.remove(a));
+
+    // as if the slot had been reused 2^32 - 1 times
+    map.slots[0].generation = u32::MAX;
+    let b = map.push("b");
+    assert_eq!(map.slots.len(), 1);
+
+    // the slot is retired rather than wrapping back to the generation of `a`
+    assert!(map.remove(b));
+    let c = map.push("c");
+    let d = map.push("d");
+    assert_eq!(map.slots.len(), 3);
+    for stale in [a, b] {
+        assert!(!map.contains(stale));
+        assert_eq!(map.get(stale), None);
+    }
+    assert_eq!(&map[c], "c");
+    assert_eq!(&map[d], "d");
+}
+
+#[test]
+fn slot_map_drop_test() {
+    use crate::HeteroSizedSlotMap;
+    use drop_test::*;
+
+    let counter = DropTestCounter::new();
+
+    let mut map: HeteroSizedSlotMap<dyn Send> = HeteroSizedSlotMap::new();
+    let mut handles = Vec::new();
+    for i in 0..30 {
+        handles.push(map.push_value(counter.token()));
+        if i % 2 == 0 {
+            handles.push(map.push_value([counter.token(), counter.token()]));
+        }
+        if i % 3 == 0 {
+            let handle = handles.remove(handles.len() / 2);
+            assert!(map.remove(handle));
+        }
+    }
+    assert_eq!(map.len(), handles.len());
+    for &handle in &handles {
+        assert!(map.contains(handle));
+    }
+
+    drop(map);
+    counter.check();
+}
+
+#[test]
+// guard bytes change the layout
+#[cfg(not(feature = "debug-checks"))]
+fn remove_and_reuse() {
+    let mut vec: HeteroSizedVec<[u64]> = HeteroSizedVec::new();
+
+    vec.push_value([0_u64; 4]);
+    vec.push_value([1_u64; 2]);
+    vec.push_value([2_u64; 4]);
+    vec.push_value([3_u64; 1]);
+    let full_len = vec.storage.len();
+
+    vec.remove(0);
+    vec.remove(1);
+    assert_eq!(vec.len(), 2);
+    assert_eq!(&vec[0], &[1; 2]);
+    assert_eq!(&vec[1], &[3; 1]);
+    assert_eq!(vec.storage.len(), full_len);
+
+    // these fit in the freed space
+    vec.push_value([4_u64; 3]);
+    vec.push_value([5_u64; 4]);
+    vec.push_value([6_u64; 1]);
+    assert_eq!(vec.storage.len(), full_len);
+
+    // this does not
+    vec.push_value([7_u64; 8]);
+    assert!(vec.storage.len() > full_len);
+
+    // removing from the end shrinks the storage
+    vec.remove(5);
+    assert_eq!(vec.storage.len(), full_len);
+
+    let elems: Vec<Vec<u64>> = vec.iter().map(|elem| elem.to_vec()).collect();
+    assert_eq!(elems, vec![
+        vec![1; 2],
+        vec![3; 1],
+        vec![4; 3],
+        vec![5; 4],
+        vec![6; 1],
+    ]);
+}
+
+#[test]
+fn defragment() {
+    use std::fmt::Debug;
+
+    let mut vec: HeteroSizedVec<dyn Debug> = HeteroSizedVec::new();
+    for i in 0..20_u32 {
+        if i % 2 == 0 {
+            vec.push_value(i as u8);
+        } else {
+            vec.push_value([i as u64; 3]);
+        }
+    }
+    for i in (0..20).rev().filter(|i| i % 3 == 0) {
+        vec.remove(i);
+    }
+    let expected: Vec<String> = vec.iter()
+        .map(|elem| format!("{:?}", elem))
+        .collect();
+
+    let old_len = vec.storage.len();
+    let reclaimed = vec.defragment();
+    assert!(reclaimed > 0);
+    assert_eq!(vec.storage.len(), old_len - reclaimed);
+    assert_eq!(vec.defragment(), 0);
+
+    let actual: Vec<String> = vec.iter()
+        .map(|elem| format!("{:?}", elem))
+        .collect();
+    assert_eq!(actual, expected);
+}
+
+#[test]
+fn defragment_mixed_align() {
+    use std::{any::Any, mem::align_of_val};
+
+    fn values(vec: &HeteroSizedVec<dyn Any>) -> Vec<u128> {
+        vec.iter()
+            .map(|elem| {
+                if let Some(&n) = elem.downcast_ref::<u8>() {
+                    n as u128
+                } else if let Some(&n) = elem.downcast_ref::<u64>() {
+                    n as u128
+                } else if let Some(a) = elem.downcast_ref::<[u8; 7]>() {
+                    a[6] as u128
+                } else {
+                    *elem.downcast_ref::<u128>().unwrap()
+                }
+            })
+            .collect()
+    }
+
+    // the `u64` pushed last reuses the first element's space, so packing in
+    // index order would need more storage than there is now
+    let mut vec: HeteroSizedVec<dyn Any> = HeteroSizedVec::new();
+    vec.push_value(1_u64);
+    vec.push_value([0_u8; 7]);
+    vec.push_value(2_u64);
+    vec.push_value([0_u8; 7]);
+    vec.remove(0);
+    vec.push_value(3_u64);
+
+    let old_len = vec.storage.len();
+    let reclaimed = vec.defragment();
+    assert_eq!(vec.storage.len(), old_len - reclaimed);
+    assert_eq!(vec.get_as::<[u8; 7]>(0), Some(&[0; 7]));
+    assert_eq!(vec.get_as::<u64>(1), Some(&2));
+    assert_eq!(vec.get_as::<[u8; 7]>(2), Some(&[0; 7]));
+    assert_eq!(vec.get_as::<u64>(3), Some(&3));
+
+    // with removals all over, the storage never grows
+    let mut vec: HeteroSizedVec<dyn Any> = HeteroSizedVec::new();
+    for i in 0..60_u32 {
+        match i % 4 {
+            0 => vec.push_value(i as u8),
+            1 => vec.push_value(i as u64),
+            2 => vec.push_value([i as u8; 7]),
+            _ => vec.push_value(i as u128),
+        }
+        if i % 5 == 4 {
+            vec.remove((i as usize * 7) % vec.len());
+        }
+        if i % 9 == 8 {
+            let expected = values(&vec);
+            let old_len = vec.storage.len();
+            let reclaimed = vec.defragment();
+            assert_eq!(vec.storage.len(), old_len - reclaimed);
+            assert_eq!(values(&vec), expected);
+        }
+    }
+    for (index, elem) in vec.iter().enumerate() {
+        let addr = (elem as *const dyn Any).addr();
+        assert_eq!(addr % align_of_val(elem), 0, "element {}", index);
+    }
+}
+
+#[test]
+fn last_resize_after_reuse() {
+    let mut vec: HeteroSizedVec<str> = HeteroSizedVec::new();
+
+    vec.push("hello");
+    vec.push("world");
+    vec.remove(0);
+
+    // goes into the freed space, before "world"
+    vec.push("abc");
+    vec.last_extend_from_slice("defg");
+    vec.last_truncate(5);
+
+    assert_eq!(&vec[0], "world");
+    assert_eq!(&vec[1], "abcde");
+}
+
+#[test]
+fn remove_drop_test() {
+    use drop_test::*;
+
+    let counter = DropTestCounter::new();
+
+    let mut vec: HeteroSizedVec<[DropTestToken]> = HeteroSizedVec::new();
+    for i in 0..50 {
+        let mut elem_vec: Vec<DropTestToken> = Vec::new();
+        for _ in 0..(i % 7) {
+            elem_vec.push(counter.token());
+        }
+        vec.push(elem_vec);
+        if i % 4 == 3 {
+            vec.remove(i / 8);
+        }
+    }
+    vec.defragment();
+    for _ in 0..10 {
+        vec.remove(0);
+    }
+
+    drop(vec);
+    counter.check();
+}
+
+pub mod alloc_test {
+    use std::{
+        alloc::{
+            AllocError,
+            Allocator,
+            Global,
+            Layout,
+        },
+        cell::Cell,
+        ptr::NonNull,
+        rc::Rc,
+    };
+
+    /// Allocator which counts its live allocations.
+    #[derive(Clone, Default)]
+    pub struct CountingAlloc { live: Rc<Cell<isize>> }
+
+    impl CountingAlloc {
+        pub fn new() -> Self {
+            CountingAlloc { live: Rc::new(Cell::new(0)) }
+        }
+
+        pub fn live(&self) -> isize {
+            self.live.get()
+        }
+    }
+
+    unsafe impl Allocator for CountingAlloc {
+        fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+            self.live.set(self.live.get() + 1);
+            Global.allocate(layout)
+        }
+
+        unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+            self.live.set(self.live.get() - 1);
+            Global.deallocate(ptr, layout)
+        }
+    }
+}
+
+#[test]
+fn custom_allocator() {
+    use alloc_test::CountingAlloc;
+
+    let alloc = CountingAlloc::new();
+
+    let mut vec: HeteroSizedVec<dyn Fn() -> usize, CountingAlloc> =
+        HeteroSizedVec::new_in(alloc.clone());
+    for n in 0..10 {
+        vec.push_value(move || n);
+    }
+    assert!(alloc.live() > 0);
+
+    let boxes: Vec<Box<dyn Fn() -> usize, CountingAlloc>> = vec.into_box_vec();
+    assert_eq!(alloc.live(), 10);
+    for (i, func) in boxes.iter().enumerate() {
+        assert_eq!(i, func());
+    }
+
+    drop(boxes);
+    assert_eq!(alloc.live(), 0);
+}
+
+#[test]
+fn custom_allocator_drop_test() {
+    use alloc_test::CountingAlloc;
+    use drop_test::*;
+
+    let alloc = CountingAlloc::new();
+    let counter = DropTestCounter::new();
+
+    let mut vec: HeteroSizedVec<[DropTestToken], CountingAlloc> =
+        HeteroSizedVec::with_type_tracking_in(alloc.clone());
+    for i in 0..20 {
+        vec.push((0..i).map(|_| counter.token()).collect::<Vec<_>>());
+    }
+    vec.remove(3);
+    vec.defragment();
+
+    drop(vec);
+    counter.check();
+    assert_eq!(alloc.live(), 0);
+}
+
+#[test]
+fn into_box_vec_over_aligned() {
+    use drop_test::*;
+
+    pub trait Align {
+        fn addr(&self) -> usize;
+
+        fn align(&self) -> usize;
+
+        fn value(&self) -> usize;
+    }
+
+    macro_rules! align_impl {
+        ($align:expr, $value:expr, $token:expr)=>{{
+            #[repr(align($align))]
+            #[allow(dead_code)]
+            struct SpecialAlign(usize, DropTestToken);
+
+            impl Align for SpecialAlign {
+                fn addr(&self) -> usize {
+                    (self as *const Self).addr()
+                }
+
+                fn align(&self) -> usize { $align }
+
+                fn value(&self) -> usize { self.0 }
+            }
+
+            SpecialAlign($value, $token)
+        }};
+    }
+
+    let counter = DropTestCounter::new();
+
+    let mut vec: HeteroSizedVec<dyn Align> = HeteroSizedVec::new();
+    vec.push_value(align_impl!(2, 0, counter.token()));
+    vec.push_value(align_impl!(64, 1, counter.token()));
+    vec.push_value(align_impl!(1, 2, counter.token()));
+    vec.push_value(align_impl!(4096, 3, counter.token()));
+    vec.push_value(align_impl!(256, 4, counter.token()));
+    vec.remove(2);
+
+    let boxes: Vec<Box<dyn Align>> = vec.into_box_vec();
+    assert_eq!(boxes.len(), 4);
+    for (elem, value) in boxes.iter().zip(&[0, 1, 3, 4]) {
+        assert_eq!(elem.addr() % elem.align(), 0);
+        assert_eq!(elem.value(), *value);
+    }
+
+    drop(boxes);
+    counter.check();
+}
+
+#[test]
+fn into_box_vec_zero_sized() {
+    use alloc_test::CountingAlloc;
+
+    pub trait Zero {
+        fn addr(&self) -> usize;
+
+        fn align(&self) -> usize;
+    }
+
+    struct Unit;
+
+    #[repr(align(64))]
+    struct Aligned;
+
+    impl Zero for Unit {
+        fn addr(&self) -> usize { (self as *const Self).addr() }
+
+        fn align(&self) -> usize { 1 }
+    }
+
+    impl Zero for Aligned {
+        fn addr(&self) -> usize { (self as *const Self).addr() }
+
+        fn align(&self) -> usize { 64 }
+    }
+
+    let alloc = CountingAlloc::new();
+
+    let mut vec: HeteroSizedVec<dyn Zero, CountingAlloc> =
+        HeteroSizedVec::new_in(alloc.clone());
+    vec.push_value(Unit);
+    vec.push_value(Aligned);
+    vec.push_value(Aligned);
+    vec.push_value(Unit);
+
+    // zero-sized boxes are not allocated
+    let boxes: Vec<Box<dyn Zero, CountingAlloc>> = vec.into_box_vec();
+    assert_eq!(alloc.live(), 0);
+    assert_eq!(boxes.len(), 4);
+    for elem in &boxes {
+        assert_eq!(elem.addr() % elem.align(), 0);
+    }
+
+    drop(boxes);
+    assert_eq!(alloc.live(), 0);
+}
+
+pub mod zst_test {
+    use std::cell::Cell;
+
+    std::thread_local! {
+        static DROPS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    /// Number of `Counted` and `CountedAligned` dropped on this thread.
+    pub fn drops() -> usize {
+        DROPS.with(Cell::get)
+    }
+
+    pub struct Counted;
+
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            DROPS.with(|drops| drops.set(drops.get() + 1));
+        }
+    }
+
+    #[repr(align(64))]
+    pub struct CountedAligned;
+
+    impl Drop for CountedAligned {
+        fn drop(&mut self) {
+            DROPS.with(|drops| drops.set(drops.get() + 1));
+        }
+    }
+}
+
+#[test]
+fn zero_sized_basic() {
+    use crate::integrity::GUARD_LEN;
+    use std::mem::align_of;
+
+    let mut funcs: HeteroSizedVec<dyn Fn() -> usize> = HeteroSizedVec::new();
+    funcs.push_value(|| 42);
+    funcs.push_value(|| 7);
+    assert_eq!(funcs.storage.len(), 0);
+    assert_eq!(funcs.storage.capacity(), 0);
+
+    let n = 3_usize;
+    funcs.push_value(move || n);
+    funcs.push_value(|| 5);
+    // guarded on both sides
+    assert_eq!(funcs.storage.len(), GUARD_LEN + 8 + GUARD_LEN);
+    assert_eq!(
+        funcs.iter().map(|func| func()).collect::<Vec<_>>(),
+        vec![42, 7, 3, 5],
+    );
+
+    // empty slices are aligned for their items
+    let mut arrays: HeteroSizedVec<[u64]> = HeteroSizedVec::new();
+    arrays.push_value([0_u64; 0]);
+    arrays.push(Vec::<u64>::new());
+    assert_eq!(arrays.storage.len(), 0);
+    for array in &arrays {
+        assert!(array.is_empty());
+        assert_eq!(array.as_ptr().addr() % align_of::<u64>(), 0);
+    }
+
+    // and grow into storage
+    arrays.last_push_item(1);
+    arrays.last_push_item(2);
+    assert_eq!(&arrays[0], &[] as &[u64]);
+    assert_eq!(&arrays[1], &[1, 2]);
+    arrays.last_truncate(0);
+    assert_eq!(&arrays[1], &[] as &[u64]);
+    assert_eq!(arrays[1].as_ptr().addr() % align_of::<u64>(), 0);
+}
+
+#[test]
+fn zero_sized_drop_test() {
+    use alloc_test::CountingAlloc;
+    use zst_test::*;
+    use std::{any::Any, mem::align_of_val};
+
+    let alloc = CountingAlloc::new();
+
+    // dropped in-place
+    let before = drops();
+    let mut vec: HeteroSizedVec<dyn Any, CountingAlloc> =
+        HeteroSizedVec::new_in(alloc.clone());
+    for _ in 0..4 {
+        vec.push_value(Counted);
+        vec.push_value(CountedAligned);
+    }
+    assert_eq!(vec.storage.capacity(), 0);
+    for elem in &vec {
+        assert_eq!((elem as *const dyn Any).addr() % align_of_val(elem), 0);
+    }
+    vec.remove(1);
+    assert_eq!(drops() - before, 1);
+    vec.defragment();
+    drop(vec);
+    assert_eq!(drops() - before, 8);
+    assert_eq!(alloc.live(), 0);
+
+    // dropped from boxes, which are not allocated
+    let before = drops();
+    let mut vec: HeteroSizedVec<dyn Any, CountingAlloc> =
+        HeteroSizedVec::new_in(alloc.clone());
+    for _ in 0..4 {
+        vec.push_value(Counted);
+        vec.push_value(CountedAligned);
+    }
+    let boxes: Vec<Box<dyn Any, CountingAlloc>> = vec.into_box_vec();
+    assert_eq!(alloc.live(), 0);
+    assert_eq!(drops() - before, 0);
+    assert!(boxes[1].is::<CountedAligned>());
+    drop(boxes);
+    assert_eq!(drops() - before, 8);
+
+    // zero-sized items of slices
+    let before = drops();
+    let mut vec: HeteroSizedVec<[Counted]> = HeteroSizedVec::new();
+    vec.push(vec![Counted, Counted, Counted]);
+    vec.last_push_item(Counted);
+    assert_eq!(vec[0].len(), 4);
+    assert_eq!(vec.storage.len(), 0);
+    vec.last_truncate(1);
+    assert_eq!(drops() - before, 3);
+    drop(vec);
+    assert_eq!(drops() - before, 4);
+}
+
+#[test]
+fn drop_elision() {
+    use drop_test::*;
+    use std::any::Any;
+
+    // nothing has a destructor
+    let mut arrays: HeteroSizedVec<[u8]> = HeteroSizedVec::new();
+    arrays.push(&[1_u8, 2] as &[_]);
+    arrays.push(vec![3_u8]);
+    arrays.push_value([4_u8; 3]);
+    arrays.last_push_item(5);
+    assert!(!arrays.needs_drop);
+    assert!(arrays.drop_handlers.iter().all(Option::is_none));
+
+    arrays.clear();
+    assert_eq!(arrays.len(), 0);
+    assert_eq!(arrays.storage.len(), 0);
+    arrays.push(&[6_u8] as &[_]);
+    assert_eq!(&arrays[0], &[6]);
+
+    let mut strs: HeteroSizedVec<str> = HeteroSizedVec::new();
+    strs.push("hello");
+    strs.last_extend_from_slice(" world");
+    assert!(!strs.needs_drop);
+    strs.clear();
+    assert_eq!(strs.len(), 0);
+
+    // only some elements have a destructor
+    let counter = DropTestCounter::new();
+    let mut vec: HeteroSizedVec<dyn Any> = HeteroSizedVec::with_type_tracking();
+    vec.push_value(1_u32);
+    vec.push_value(counter.token());
+    vec.push_value([2_u64; 4]);
+    vec.push_value(counter.token());
+    assert!(vec.needs_drop);
+    assert!(vec.drop_handlers[0].is_none());
+    assert!(vec.drop_handlers[1].is_some());
+
+    vec.clear();
+    counter.check();
+    assert!(!vec.needs_drop);
+    assert_eq!(vec.len(), 0);
+
+    vec.push_value(counter.token());
+    vec.push_value(3_u32);
+    assert_eq!(vec[1].downcast_ref::<u32>(), Some(&3));
+    drop(vec);
+    counter.check();
+}
+
+#[test]
+fn array_vec_basic() {
+    use crate::HeteroSizedArrayVec;
+
+    let mut vec: HeteroSizedArrayVec<dyn Fn() -> u64, 64, 4> =
+        HeteroSizedArrayVec::new();
+
+    let a = 1_u64;
+    let b = [2_u64, 3];
+    assert!(vec.try_push_value(move || a).is_ok());
+    assert!(vec.try_push_value(move || b[0] + b[1]).is_ok());
+    assert!(vec.try_push_value(|| 4).is_ok());
+    assert_eq!(vec.len(), 3);
+
+    // too many bytes
+    let c = [0_u64; 8];
+    assert!(vec.try_push_value(move || c[0]).is_err());
+    assert_eq!(vec.len(), 3);
+
+    let results: Vec<u64> = vec.iter().map(|func| func()).collect();
+    assert_eq!(results, vec![1, 5, 4]);
+
+    // too many elements
+    assert!(vec.try_push_value(|| 6).is_ok());
+    assert!(vec.try_push_value(|| 7).is_err());
+    assert_eq!(vec.len(), 4);
+}
+
+#[test]
+fn array_vec_overflow_returns_elem() {
+    use crate::HeteroSizedArrayVec;
+
+    let mut vec: HeteroSizedArrayVec<[u32], 16, 2> = HeteroSizedArrayVec::new();
+
+    assert_eq!(vec.try_push(vec![1_u32, 2, 3]), Ok(()));
+    assert_eq!(vec.try_push(vec![4_u32, 5]), Err(vec![4, 5]));
+    assert_eq!(vec.try_push(vec![4_u32]), Ok(()));
+    assert_eq!(vec.try_push(Vec::<u32>::new()), Err(vec![]));
+
+    #[repr(align(32))]
+    #[allow(dead_code)]
+    struct OverAligned([u32; 1]);
+
+    let mut vec: HeteroSizedArrayVec<dyn Send, 64, 2> = HeteroSizedArrayVec::new();
+    assert!(vec.try_push_value(OverAligned([1])).is_err());
+
+    let mut vec: HeteroSizedArrayVec<[u32], 16, 2> = HeteroSizedArrayVec::new();
+    vec.try_push_value([1_u32, 2]).unwrap();
+    vec.try_push_value([3_u32]).unwrap();
+    for elem in &mut vec {
+        elem[0] *= 10;
+    }
+    assert_eq!(&vec[0], &[10, 2]);
+    assert_eq!(&vec[1], &[30]);
+}
+
+#[test]
+fn array_vec_drop_test() {
+    use crate::HeteroSizedArrayVec;
+    use drop_test::*;
+
+    let counter = DropTestCounter::new();
+
+    let mut vec: HeteroSizedArrayVec<dyn Send, 256, 8> =
+        HeteroSizedArrayVec::new();
+    let mut rejected = Vec::new();
+    for _ in 0..12 {
+        if let Err(token) = vec.try_push_value(counter.token()) {
+            rejected.push(token);
+        }
+    }
+    assert_eq!(vec.len(), 8);
+    assert_eq!(rejected.len(), 4);
+
+    drop(rejected);
+    drop(vec);
+    counter.check();
+}
+
+#[test]
+fn small_vec_spill() {
+    use crate::HeteroSizedSmallVec;
+
+    let mut vec: HeteroSizedSmallVec<dyn Fn() -> usize, 32, 3> =
+        HeteroSizedSmallVec::new();
+
+    for n in 0..3 {
+        vec.push_value(move || n);
+    }
+    assert!(vec.is_inline());
+
+    for n in 3..10 {
+        vec.push_value(move || n);
+    }
+    assert!(!vec.is_inline());
+    assert_eq!(vec.len(), 10);
+
+    for (i, func) in vec.iter().enumerate() {
+        assert_eq!(i, func());
+    }
+
+    let mut vec: HeteroSizedSmallVec<str, 8, 4> = HeteroSizedSmallVec::new();
+    vec.push("abc");
+    vec.push("defg");
+    assert!(vec.is_inline());
+    vec.push("hi");
+    assert!(!vec.is_inline());
+    assert_eq!(format!("{:?}", vec), r#"["abc", "defg", "hi"]"#);
+}
+
+#[test]
+fn small_vec_drop_test() {
+    use crate::HeteroSizedSmallVec;
+    use drop_test::*;
+
+    let counter = DropTestCounter::new();
+
+    let mut vec: HeteroSizedSmallVec<[DropTestToken], 64, 4> =
+        HeteroSizedSmallVec::new();
+    for i in 0..10 {
+        vec.push((0..i).map(|_| counter.token()).collect::<Vec<_>>());
+        if i == 2 {
+            assert!(vec.is_inline());
+        }
+    }
+    assert!(!vec.is_inline());
+    for (i, elem) in vec.iter().enumerate() {
+        assert_eq!(elem.len(), i);
+    }
+
+    drop(vec);
+    counter.check();
+
+    let vec: HeteroSizedSmallVec<dyn Send, 64, 4> = {
+        let mut vec = HeteroSizedSmallVec::new();
+        vec.push_value(counter.token());
+        vec
+    };
+    assert!(vec.is_inline());
+    drop(vec);
+    counter.check();
+}
+
+#[test]
+fn box_inline_and_spilled() {
+    use crate::HeteroSizedBox;
+
+    struct Handler {
+        on_event: HeteroSizedBox<dyn FnMut(u32) -> u32, 16>,
+    }
+
+    let mut total = 0;
+    let mut handler = Handler {
+        on_event: HeteroSizedBox::new_value(move |n| {
+            total += n;
+            total
+        }),
+    };
+    assert!(handler.on_event.is_inline());
+    assert_eq!((handler.on_event)(3), 3);
+    assert_eq!((handler.on_event)(4), 7);
+
+    let big = [1_u32; 8];
+    let spilled: HeteroSizedBox<dyn Fn() -> u32, 16> =
+        HeteroSizedBox::new_value(move || big.iter().sum());
+    assert!(!spilled.is_inline());
+    assert_eq!(spilled(), 8);
+
+    assert!(HeteroSizedBox::<dyn Fn() -> u32, 16>::try_new_value(move || big[0]).is_err());
+
+    let s: HeteroSizedBox<str, 8> = HeteroSizedBox::new("hello");
+    assert!(s.is_inline());
+    assert_eq!(&*s, "hello");
+    assert_eq!(format!("{:?}", s), "\"hello\"");
+
+    let mut slice: HeteroSizedBox<[u64], 8> = HeteroSizedBox::new(vec![1, 2, 3]);
+    assert!(!slice.is_inline());
+    slice[1] = 20;
+    assert_eq!(&*slice, &[1, 20, 3]);
+}
+
+#[test]
+fn box_drop_test() {
+    use crate::HeteroSizedBox;
+    use drop_test::*;
+
+    let counter = DropTestCounter::new();
+
+    let inline: HeteroSizedBox<dyn Send, 64> =
+        HeteroSizedBox::new_value(counter.token());
+    assert!(inline.is_inline());
+
+    let spilled: HeteroSizedBox<[DropTestToken], 8> =
+        HeteroSizedBox::new((0..10).map(|_| counter.token()).collect::<Vec<_>>());
+    assert!(!spilled.is_inline());
+
+    let moved = vec![inline];
+    drop(moved);
+    drop(spilled);
+    counter.check();
+}
+
+#[test]
+fn deque_fifo() {
+    use crate::HeteroSizedVecDeque;
+
+    let mut queue: HeteroSizedVecDeque<dyn FnOnce() -> usize> =
+        HeteroSizedVecDeque::new();
+
+    // go around the ring buffer many times, with varying sizes
+    let mut next_push = 0;
+    let mut next_pop = 0;
+    for round in 0..50 {
+        for _ in 0..(round % 7) {
+            let n = next_push;
+            match n % 3 {
+                0 => queue.push_back_value(move || n),
+                1 => {
+                    let pad = [n as u8; 13];
+                    queue.push_back_value(move || pad[0] as usize + n - pad[12] as usize)
+                },
+                _ => {
+                    let pad = [n as u64; 5];
+                    queue.push_back_value(move || pad[4] as usize)
+                },
+            }
+            next_push += 1;
+        }
+        for _ in 0..(round % 5) {
+            match queue.pop_front() {
+                Some(func) => {
+                    assert_eq!(func(), next_pop);
+                    next_pop += 1;
+                },
+                None => assert_eq!(next_pop, next_push),
+            }
+        }
+        assert_eq!(queue.len(), next_push - next_pop);
+    }
+    while let Some(func) = queue.pop_front() {
+        assert_eq!(func(), next_pop);
+        next_pop += 1;
+    }
+    assert_eq!(next_pop, next_push);
+}
+
+#[test]
+fn deque_both_ends() {
+    use crate::HeteroSizedVecDeque;
+
+    let mut deque: HeteroSizedVecDeque<[u16]> = HeteroSizedVecDeque::new();
+    for i in 0..40_u16 {
+        let elem = vec![i; i as usize % 9];
+        if i % 2 == 0 {
+            deque.push_back(elem);
+        } else {
+            deque.push_front(elem);
+        }
+    }
+    let lens: Vec<usize> = deque.iter().map(|elem| elem.len()).collect();
+    let expected: Vec<usize> = (0..40).rev().filter(|i| i % 2 == 1)
+        .chain((0..40).filter(|i| i % 2 == 0))
+        .map(|i| i % 9)
+        .collect();
+    assert_eq!(lens, expected);
+
+    assert_eq!(&*deque.pop_back().unwrap(), &[38; 2]);
+    assert_eq!(&*deque.pop_front().unwrap(), &[39; 3]);
+    assert_eq!(deque.front(), Some(&[37_u16; 1][..]));
+    assert_eq!(deque.back(), Some(&[36_u16; 0][..]));
+
+    #[repr(align(64))]
+    #[derive(Debug)]
+    #[allow(dead_code)]
+    struct OverAligned(u8);
+
+    let mut deque: HeteroSizedVecDeque<dyn core::fmt::Debug> =
+        HeteroSizedVecDeque::new();
+    deque.push_back_value(1_u8);
+    deque.push_front_value(OverAligned(2));
+    deque.push_back_value("three");
+    for elem in &deque {
+        let addr = elem as *const dyn core::fmt::Debug as *const u8 as usize;
+        assert_eq!(addr % core::mem::align_of_val(elem), 0);
+    }
+    assert_eq!(format!("{:?}", deque), r#"[OverAligned(2), 1, "three"]"#);
+}
+
+#[test]
+fn deque_drop_test() {
+    use crate::HeteroSizedVecDeque;
+    use drop_test::*;
+
+    let counter = DropTestCounter::new();
+
+    let mut deque: HeteroSizedVecDeque<[DropTestToken]> =
+        HeteroSizedVecDeque::new();
+    for i in 0..30 {
+        let elem: Vec<DropTestToken> = (0..i % 4).map(|_| counter.token()).collect();
+        if i % 3 == 0 {
+            deque.push_front(elem);
+        } else {
+            deque.push_back(elem);
+        }
+        if i % 4 == 0 {
+            drop(deque.pop_front());
+        }
+        if i % 5 == 2 {
+            assert!(deque.pop_front_with(|elem| elem.len()).is_some());
+        }
+    }
+    drop(deque.pop_back());
+
+    drop(deque);
+    counter.check();
+}
+
+#[test]
+fn concurrent_push() {
+    use crate::ConcurrentHeteroSizedVec;
+    use std::{sync::Arc, thread};
+
+    const THREADS: usize = 4;
+    // miri is slow, so it gets fewer elements
+    const PER_THREAD: usize = if cfg!(miri) { 50 } else { 2000 };
+
+    let vec: Arc<ConcurrentHeteroSizedVec<dyn Fn() -> usize + Send + Sync>> =
+        Arc::new(ConcurrentHeteroSizedVec::new());
+
+    let first: usize = vec.push_value(|| usize::MAX);
+    let first_addr = &vec[first] as *const _ as *const u8;
+
+    let pushers: Vec<_> = (0..THREADS)
+        .map(|t| {
+            let vec = vec.clone();
+            thread::spawn(move || {
+                for i in 0..PER_THREAD {
+                    let n = t * PER_THREAD + i;
+                    match i % 3 {
+                        0 => vec.push_value(move || n),
+                        1 => {
+                            let pad = [n as u8; 7];
+                            vec.push_value(move || n + pad[0] as usize - pad[6] as usize)
+                        },
+                        _ => {
+                            let big = [n; 9];
+                            vec.push_value(move || big[8])
+                        },
+                    };
+                }
+            })
+        })
+        .collect();
+
+    // read concurrently with the pushes
+    let reader = {
+        let vec = vec.clone();
+        thread::spawn(move || {
+            for _ in 0..20 {
+                for func in vec.iter().skip(1) {
+                    assert!(func() < THREADS * PER_THREAD);
+                }
+            }
+        })
+    };
+
+    for pusher in pushers {
+        pusher.join().unwrap();
+    }
+    reader.join().unwrap();
+
+    assert_eq!(vec.len(), THREADS * PER_THREAD + 1);
+    assert_eq!(&vec[first] as *const _ as *const u8, first_addr);
+
+    let mut results: Vec<usize> = vec.iter().skip(1).map(|func| func()).collect();
+    results.sort();
+    assert_eq!(results, (0..THREADS * PER_THREAD).collect::<Vec<_>>());
+}
+
+#[test]
+fn concurrent_drop_test() {
+    use crate::ConcurrentHeteroSizedVec;
+    use drop_test::*;
+    use std::{sync::Arc, thread};
+
+    let counter = DropTestCounter::new();
+
+    const PER_THREAD: usize = if cfg!(miri) { 20 } else { 200 };
+
+    let vec: Arc<ConcurrentHeteroSizedVec<[DropTestToken]>> =
+        Arc::new(ConcurrentHeteroSizedVec::new());
+    let pushers: Vec<_> = (0..4)
+        .map(|_| {
+            let vec = vec.clone();
+            let counter = counter.clone();
+            thread::spawn(move || {
+                for i in 0..PER_THREAD {
+                    vec.push((0..i % 5).map(|_| counter.token()).collect::<Vec<_>>());
+                }
+            })
+        })
+        .collect();
+    for pusher in pushers {
+        pusher.join().unwrap();
+    }
+    assert_eq!(vec.iter().count(), 4 * PER_THREAD);
+
+    drop(vec);
+    counter.check();
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn channel_messages() {
+    use crate::hetero_channel;
+    use std::{sync::mpsc::TryRecvError, thread};
+
+    trait Message: Send {
+        fn value(&self) -> usize;
+    }
+
+    impl Message for usize {
+        fn value(&self) -> usize { *self }
+    }
+
+    impl Message for [usize; 6] {
+        fn value(&self) -> usize { self[5] }
+    }
+
+    let (tx, mut rx) = hetero_channel::<dyn Message>();
+    assert!(matches!(rx.try_recv(), Err(TryRecvError::Empty)));
+
+    const PER_SENDER: usize = if cfg!(miri) { 50 } else { 500 };
+
+    let senders: Vec<_> = (0..3)
+        .map(|t| {
+            let tx = tx.clone();
+            thread::spawn(move || {
+                for i in 0..PER_SENDER {
+                    let n = t * 1000 + i;
+                    if i % 2 == 0 {
+                        tx.send_value(n).unwrap();
+                    } else {
+                        tx.send_value([n; 6]).unwrap();
+                    }
+                }
+            })
+        })
+        .collect();
+    drop(tx);
+
+    // messages from each sender arrive in order
+    let mut next = [0, 1000, 2000];
+    let mut received = 0;
+    loop {
+        let result = match received % 2 {
+            0 => rx.recv().map(|msg| msg.value()),
+            _ => rx.recv_with(|msg| msg.value()),
+        };
+        let n = match result {
+            Ok(n) => n,
+            Err(_) => break,
+        };
+        assert_eq!(n, next[n / 1000]);
+        next[n / 1000] += 1;
+        received += 1;
+    }
+    assert_eq!(received, 3 * PER_SENDER);
+
+    for sender in senders {
+        sender.join().unwrap();
+    }
+    assert!(matches!(rx.try_recv(), Err(TryRecvError::Disconnected)));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn channel_drop_test() {
+    use crate::hetero_channel;
+    use drop_test::*;
+
+    let counter = DropTestCounter::new();
+
+    let (tx, mut rx) = hetero_channel::<[DropTestToken]>();
+    for i in 0..20 {
+        tx.send((0..i % 4).map(|_| counter.token()).collect::<Vec<_>>()).unwrap();
+    }
+    assert_eq!(rx.recv().unwrap().len(), 0);
+    assert_eq!(rx.recv_with(|msg| msg.len()).unwrap(), 1);
+    tx.send(vec![counter.token()]).unwrap();
+
+    // undelivered messages are dropped with the receiver
+    drop(rx);
+    let rejected = tx.send(vec![counter.token()]).unwrap_err().0;
+    assert_eq!(rejected.len(), 1);
+    drop(rejected);
+    drop(tx);
+
+    counter.check();
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn channel_survives_panicking_sender() {
+    use crate::{hetero_channel, HeteroSizedPush};
+    use std::thread;
+
+    // a message which panics while being sent, with the lock held
+    struct Panicking;
+
+    unsafe impl HeteroSizedPush<str> for Panicking {
+        unsafe fn elem_size(&self) -> usize { panic!("elem_size") }
+
+        unsafe fn elem_align(&self) -> usize { 1 }
+
+        unsafe fn elem_ptr(&self) -> *const str { "" }
+
+        unsafe fn elem_drop_handler(&self) -> Option<fn(*mut u8, usize)> { None }
+
+        unsafe fn outer_drop(&mut self) {}
+    }
+
+    let (tx, mut rx) = hetero_channel::<str>();
+    let panicking = tx.clone();
+    let result = thread::spawn(move || {
+        let _ = panicking.send(Panicking);
+    }).join();
+    assert!(result.is_err());
+
+    tx.send("after").unwrap();
+    drop(tx);
+    assert_eq!(&*rx.recv().unwrap(), "after");
+    assert!(rx.recv().is_err());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn serialize_round_trip() {
+    let mut strs: HeteroSizedVec<str> = HeteroSizedVec::new();
+    for s in &["hello", "", "wörld", "a longer string"] {
+        strs.push(*s);
+    }
+    strs.remove(1);
+
+    let mut bytes: Vec<u8> = Vec::new();
+    strs.write_to(&mut bytes).unwrap();
+    let read = HeteroSizedVec::<str>::read_from(&bytes[..]).unwrap();
+    assert_eq!(read.iter().collect::<Vec<_>>(), vec!["hello", "wörld", "a longer string"]);
+
+    let mut rows: HeteroSizedVec<[u32]> = HeteroSizedVec::new();
+    for i in 0..10_u32 {
+        rows.push((0..i).collect::<Vec<u32>>());
+    }
+    let mut bytes: Vec<u8> = Vec::new();
+    rows.write_to(&mut bytes).unwrap();
+    let mut read = HeteroSizedVec::<[u32]>::read_from(&bytes[..]).unwrap();
+    assert_eq!(read.len(), 10);
+    for (i, row) in read.iter().enumerate() {
+        assert_eq!(row, &(0..i as u32).collect::<Vec<u32>>()[..]);
+    }
+    // the read vector can be used as normal
+    read.push(vec![7_u32; 3]);
+    read.remove(0);
+    assert_eq!(&read[9], &[7, 7, 7]);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn serialize_ref_zero_copy() {
+    use crate::{HeteroSizedVecRef, DecodeError};
+
+    let mut strs: HeteroSizedVec<str> = HeteroSizedVec::new();
+    strs.push("one");
+    strs.push("two");
+    let mut bytes: Vec<u8> = Vec::new();
+    strs.write_to(&mut bytes).unwrap();
+
+    let view = HeteroSizedVecRef::<str>::from_bytes(&bytes).unwrap();
+    assert_eq!(view.len(), 2);
+    assert_eq!(&view[1], "two");
+    assert_eq!(view.iter().collect::<Vec<_>>(), vec!["one", "two"]);
+    let range = bytes.as_ptr_range();
+    assert!(range.contains(&view.get(0).unwrap().as_ptr()));
+
+    assert_eq!(
+        HeteroSizedVecRef::<str>::from_bytes(&bytes[..bytes.len() - 1]).err(),
+        Some(DecodeError::Truncated),
+    );
+    assert_eq!(
+        HeteroSizedVecRef::<str>::from_bytes(&bytes[1..]).err(),
+        Some(DecodeError::BadMagic),
+    );
+    assert_eq!(
+        HeteroSizedVecRef::<[u16]>::from_bytes(&bytes).err(),
+        Some(DecodeError::ElemTypeMismatch),
+    );
+    let mut corrupt = bytes.clone();
+    *corrupt.last_mut().unwrap() = 0xff;
+    assert_eq!(
+        HeteroSizedVecRef::<str>::from_bytes(&corrupt).err(),
+        Some(DecodeError::InvalidUtf8(1)),
+    );
+
+    let mut rows: HeteroSizedVec<[u64]> = HeteroSizedVec::new();
+    rows.push(vec![1_u64, 2]);
+    rows.push(vec![3_u64]);
+    let mut bytes: Vec<u8> = vec![0];
+    rows.write_to(&mut bytes).unwrap();
+
+    // copy into 8-aligned memory
+    let mut aligned: Vec<u64> = vec![0; bytes.len() / 8 + 1];
+    let aligned: &mut [u8] = unsafe {
+        std::slice::from_raw_parts_mut(aligned.as_mut_ptr() as *mut u8, bytes.len() - 1)
+    };
+    aligned.copy_from_slice(&bytes[1..]);
+    let view = HeteroSizedVecRef::<[u64]>::from_bytes(aligned).unwrap();
+    assert_eq!(view.iter().collect::<Vec<_>>(), vec![&[1, 2][..], &[3][..]]);
+
+    if bytes.as_ptr().addr().is_multiple_of(8) {
+        assert_eq!(
+            HeteroSizedVecRef::<[u64]>::from_bytes(&bytes[1..]).err(),
+            Some(DecodeError::Misaligned(0)),
+        );
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn serialize_ref_validation() {
+    use crate::{HeteroSizedVecRef, DecodeError};
+
+    let mut rows: HeteroSizedVec<[u8]> = HeteroSizedVec::new();
+    for i in 0..100_u8 {
+        rows.push(vec![i; i as usize % 13]);
+    }
+    let mut bytes: Vec<u8> = Vec::new();
+    rows.write_to(&mut bytes).unwrap();
+
+    let view = HeteroSizedVecRef::<[u8]>::from_bytes(&bytes).unwrap();
+    let copy = view;
+    assert_eq!(copy.len(), 100);
+    assert_eq!(copy.get(99), Some(&[99; 8][..]));
+    assert_eq!(copy.get(100), None);
+    for (i, row) in view.iter().enumerate() {
+        assert_eq!(row, &vec![i as u8; i % 13][..]);
+    }
+
+    // point the last element past the end of the data
+    let offsets_start = 32;
+    let last = offsets_start + 99 * 8;
+    let mut corrupt = bytes.clone();
+    corrupt[last..last + 8].copy_from_slice(&(bytes.len() as u64).to_le_bytes());
+    assert_eq!(
+        HeteroSizedVecRef::<[u8]>::from_bytes(&corrupt).err(),
+        Some(DecodeError::BadElem(99)),
+    );
+
+    // make the second element overlap the first
+    let mut corrupt = bytes.clone();
+    corrupt[offsets_start + 16..offsets_start + 24].copy_from_slice(&0_u64.to_le_bytes());
+    assert_eq!(
+        HeteroSizedVecRef::<[u8]>::from_bytes(&corrupt).err(),
+        Some(DecodeError::BadElem(2)),
+    );
+}
+
+#[test]
+// guard bytes change the layout
+#[cfg(not(feature = "debug-checks"))]
+fn pod_as_bytes() {
+    let mut rows: HeteroSizedVec<[u16]> = HeteroSizedVec::new();
+    rows.push(vec![1_u16]);
+    rows.push(vec![2_u16, 3]);
+    assert_eq!(rows.as_bytes().len(), 6);
+    assert_eq!(u16::from_ne_bytes([rows.as_bytes()[2], rows.as_bytes()[3]]), 2);
+
+    let mut bytes: HeteroSizedVec<[u8]> = HeteroSizedVec::new();
+    bytes.push(vec![0xff_u8; 3]);
+    bytes.extend_from_bytes(&[0xee; 2]);
+    assert_eq!(bytes.as_bytes(), &[0xff, 0xff, 0xff, 0xee, 0xee]);
+
+    let mut strs: HeteroSizedVec<str> = HeteroSizedVec::new();
+    strs.push("foo");
+    strs.push("bar");
+    assert_eq!(strs.as_bytes(), b"foobar");
+}
+
+#[test]
+fn pod_extend_from_bytes() {
+    let items: [u32; 3] = [1, 0xdeadbeef, 3];
+    let mut raw: Vec<u8> = vec![0];
+    for item in &items {
+        raw.extend_from_slice(&item.to_ne_bytes());
+    }
+
+    let mut rows: HeteroSizedVec<[u32]> = HeteroSizedVec::new();
+    rows.push(vec![7_u32]);
+    // the bytes are deliberately misaligned
+    rows.extend_from_bytes(&raw[1..]);
+    rows.extend_from_bytes(&[]);
+    assert_eq!(rows.len(), 3);
+    assert_eq!(&rows[1], &items[..]);
+    assert_eq!(&rows[2], &[] as &[u32]);
+}
+
+#[test]
+#[should_panic]
+fn pod_extend_from_bytes_bad_len() {
+    let mut rows: HeteroSizedVec<[u32]> = HeteroSizedVec::new();
+    rows.extend_from_bytes(&[0; 5]);
+}
+
+#[test]
+// guard bytes change the layout
+#[cfg(not(feature = "debug-checks"))]
+fn layout_report_padding() {
+    use std::{
+        fmt::Debug,
+        mem::size_of,
+    };
+
+    let mut vec: HeteroSizedVec<[u8]> = HeteroSizedVec::new();
+    vec.push(vec![1_u8; 3]);
+    vec.push(vec![2_u8; 5]);
+    vec.push(vec![3_u8; 2]);
+    vec.remove(1);
+
+    let report = vec.layout_report();
+    assert_eq!(report.elems.len(), 2);
+    assert_eq!(report.elems[1].offset, 8);
+    assert_eq!(report.elems[1].ptr_meta, 2);
+    assert_eq!(report.live_bytes, 5);
+    assert_eq!(report.free_bytes, 5);
+    assert_eq!(report.padding_bytes, 0);
+
+    let mut vec: HeteroSizedVec<dyn Debug> = HeteroSizedVec::new();
+    vec.push_value(1_u8);
+    vec.push_value(2_u32);
+    vec.push_value(3_u16);
+    vec.push_value(4_u64);
+
+    let report = vec.layout_report();
+    let offsets: Vec<usize> = report.elems.iter().map(|elem| elem.offset).collect();
+    let padding: Vec<usize> = report.elems.iter().map(|elem| elem.padding).collect();
+    assert_eq!(offsets, vec![0, 4, 8, 16]);
+    assert_eq!(padding, vec![0, 3, 0, 6]);
+    assert_eq!(report.elems[3].align, 8);
+    assert_eq!(report.live_bytes, 15);
+    assert_eq!(report.padding_bytes, 9);
+    assert_eq!(report.buffers[0].name, "storage");
+    assert_eq!(report.buffers[0].used, 24);
+    assert!(report.metadata_bytes >= 4 * 4 * size_of::<usize>());
+
+    let table = format!("{}", report);
+    assert!(table.contains("padding bytes:  9"));
+    assert!(table.contains("storage"));
+}
+
+#[test]
+#[cfg(all(feature = "std", feature = "debug-checks"))]
+#[should_panic(expected = "past the end of element 1")]
+fn debug_check_overflow() {
+    let mut strs: HeteroSizedVec<str> = HeteroSizedVec::new();
+    strs.push("hello");
+    strs.push("world");
+    strs.push("2+2=4");
+    {
+        let elem: &mut str = &mut strs[1];
+        unsafe {
+            let bytes: *mut u8 = elem.as_bytes_mut().as_mut_ptr();
+            *bytes.add(5) = b'!';
+        }
+    }
+    strs.push("foo");
+}
+
+#[test]
+#[cfg(all(feature = "std", feature = "debug-checks"))]
+#[should_panic(expected = "past the end of element 1")]
+fn debug_check_overflow_last() {
+    let mut strs: HeteroSizedVec<str> = HeteroSizedVec::new();
+    strs.push("hello");
+    strs.push("world");
+    {
+        let elem: &mut str = &mut strs[1];
+        unsafe {
+            let bytes: *mut u8 = elem.as_bytes_mut().as_mut_ptr();
+            *bytes.add(5) = b'!';
+        }
+    }
+    strs.debug_check();
+}
+
+#[test]
+#[cfg(all(feature = "std", feature = "debug-checks"))]
+#[should_panic(expected = "past the end of element 1")]
+fn debug_check_overflow_last_grown() {
+    let mut rows: HeteroSizedVec<[u64]> = HeteroSizedVec::new();
+    rows.push(vec![1_u64, 2]);
+    rows.push(vec![3_u64]);
+    rows.last_extend_from_slice(&[4, 5]);
+    rows.remove(0);
+    rows.push(vec![6_u64]);
+    rows.last_push_item(7);
+    rows.defragment();
+    {
+        let row: &mut [u64] = &mut rows[1];
+        unsafe {
+            *row.as_mut_ptr().add(2) = 8;
+        }
+    }
+    rows.debug_check();
+}
+
+#[test]
+#[cfg(all(feature = "std", feature = "debug-checks"))]
+#[should_panic(expected = "past the end of element 0")]
+fn debug_check_overflow_last_shrunk() {
+    let mut rows: HeteroSizedVec<[u64]> = HeteroSizedVec::new();
+    rows.push(vec![1_u64, 2, 3, 4]);
+    rows.last_truncate(1);
+    {
+        let row: &mut [u64] = &mut rows[0];
+        unsafe {
+            *row.as_mut_ptr().add(1) = 5;
+        }
+    }
+    rows.debug_check();
+}
+
+#[test]
+#[cfg(feature = "debug-checks")]
+fn debug_check_reuse() {
+    let mut vec: HeteroSizedVec<[u64]> = HeteroSizedVec::new();
+    for i in 0..10_u64 {
+        vec.push(vec![i; i as usize]);
+    }
+    for i in (0..10).step_by(3).rev() {
+        vec.remove(i);
+    }
+    for i in 0..10_u64 {
+        vec.push(vec![i; 2]);
+    }
+    vec.last_push_item(7);
+    vec.last_truncate(1);
+    vec.defragment();
+    vec.debug_check();
+    for elem in &mut vec {
+        for item in elem {
+            *item += 1;
+        }
+    }
+    vec.debug_check();
+}
+
+pub mod model_test {
+    use super::drop_test::*;
+    use crate::HeteroSizedVec;
+
+    #[cfg(not(feature = "std"))]
+    use std::{
+        vec::Vec,
+        boxed::Box,
+        string::{
+            String,
+            ToString,
+        },
+        borrow::ToOwned,
+    };
+    use std::mem::{
+        align_of_val,
+        take,
+    };
+
+    /// Seedable xorshift pseudo-random number generator.
+    pub struct Rng(u64);
+
+    impl Rng {
+        pub fn new(seed: u64) -> Self {
+            // the state must not be zero
+            Rng(seed.wrapping_mul(0x9e3779b97f4a7c15) | 1)
+        }
+
+        pub fn next_u64(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        /// Random number in `0..n`.
+        pub fn below(&mut self, n: usize) -> usize {
+            (self.next_u64() % n as u64) as usize
+        }
+    }
+
+    /// Element type of both the vector and the model.
+    pub trait Elem {
+        fn value(&self) -> u64;
+
+        fn bump(&mut self);
+
+        /// Push an equal element, with its own token if it has one, onto `vec`.
+        fn push_copy(&self, vec: &mut HeteroSizedVec<dyn Elem>, counter: &DropTestCounter);
+    }
+
+    // the tokens are only there to be dropped
+    #[allow(dead_code)]
+    struct Byte(u8, DropTestToken);
+
+    #[allow(dead_code)]
+    struct Wide([u64; 3], DropTestToken);
+
+    #[allow(dead_code)]
+    struct Text(String, DropTestToken);
+
+    // without a token, so it's aligned to 1, and isn't dropped
+    struct Tiny(u8);
+
+    #[allow(dead_code)]
+    #[repr(align(32))]
+    struct Aligned(u64, DropTestToken);
+
+    // zero-sized, so it takes no storage
+    struct Unit;
+
+    impl Elem for Byte {
+        fn value(&self) -> u64 {
+            self.0 as u64
+        }
+
+        fn bump(&mut self) {
+            self.0 = self.0.wrapping_add(1);
+        }
+
+        fn push_copy(&self, vec: &mut HeteroSizedVec<dyn Elem>, counter: &DropTestCounter) {
+            vec.push_value(Byte(self.0, counter.token()));
+        }
+    }
+
+    impl Elem for Wide {
+        fn value(&self) -> u64 {
+            self.0[0] ^ self.0[1] ^ self.0[2]
+        }
+
+        fn bump(&mut self) {
+            self.0[2] += 1;
+        }
+
+        fn push_copy(&self, vec: &mut HeteroSizedVec<dyn Elem>, counter: &DropTestCounter) {
+            vec.push_value(Wide(self.0, counter.token()));
+        }
+    }
+
+    impl Elem for Text {
+        fn value(&self) -> u64 {
+            self.0.parse().unwrap()
+        }
+
+        fn bump(&mut self) {
+            self.0 = (self.value() + 1).to_string();
+        }
+
+        fn push_copy(&self, vec: &mut HeteroSizedVec<dyn Elem>, counter: &DropTestCounter) {
+            vec.push_value(Text(self.0.to_owned(), counter.token()));
+        }
+    }
+
+    impl Elem for Tiny {
+        fn value(&self) -> u64 {
+            self.0 as u64
+        }
+
+        fn bump(&mut self) {
+            self.0 = self.0.wrapping_add(1);
+        }
+
+        fn push_copy(&self, vec: &mut HeteroSizedVec<dyn Elem>, _: &DropTestCounter) {
+            vec.push_value(Tiny(self.0));
+        }
+    }
+
+    impl Elem for Aligned {
+        fn value(&self) -> u64 {
+            self.0
+        }
+
+        fn bump(&mut self) {
+            self.0 += 1;
+        }
+
+        fn push_copy(&self, vec: &mut HeteroSizedVec<dyn Elem>, counter: &DropTestCounter) {
+            vec.push_value(Aligned(self.0, counter.token()));
+        }
+    }
+
+    impl Elem for Unit {
+        fn value(&self) -> u64 {
+            0
+        }
+
+        fn bump(&mut self) {}
+
+        fn push_copy(&self, vec: &mut HeteroSizedVec<dyn Elem>, _: &DropTestCounter) {
+            vec.push_value(Unit);
+        }
+    }
+
+    fn random_elem(rng: &mut Rng, counter: &DropTestCounter) -> Box<dyn Elem> {
+        let n: u64 = rng.next_u64() % 1000;
+        match rng.below(6) {
+            0 => Box::new(Byte(n as u8, counter.token())),
+            1 => Box::new(Wide([n, n * 2, n * 3], counter.token())),
+            2 => Box::new(Text(n.to_string(), counter.token())),
+            3 => Box::new(Tiny(n as u8)),
+            4 => Box::new(Aligned(n, counter.token())),
+            _ => Box::new(Unit),
+        }
+    }
+
+    fn check(vec: &HeteroSizedVec<dyn Elem>, model: &[Box<dyn Elem>], seed: u64, step: usize) {
+        assert_eq!(vec.len(), model.len(), "seed {}, step {}", seed, step);
+        for (i, elem) in vec.iter().enumerate() {
+            assert_eq!(
+                elem.value(), model[i].value(),
+                "seed {}, step {}, index {}", seed, step, i,
+            );
+            let addr = elem as *const dyn Elem as *const u8 as usize;
+            assert_eq!(
+                addr % align_of_val(elem), 0,
+                "seed {}, step {}, index {} is misaligned", seed, step, i,
+            );
+        }
+    }
+
+    /// Apply the same random operations to a vector and to a model of it,
+    /// and check that they agree after each one.
+    pub fn run(seed: u64, steps: usize) {
+        let counter = DropTestCounter::new();
+        let mut rng = Rng::new(seed);
+
+        let mut vec: HeteroSizedVec<dyn Elem> = HeteroSizedVec::new();
+        let mut model: Vec<Box<dyn Elem>> = Vec::new();
+
+        for step in 0..steps {
+            match rng.below(10) {
+                0..=3 => {
+                    let elem = random_elem(&mut rng, &counter);
+                    elem.push_copy(&mut vec, &counter);
+                    model.push(elem);
+                },
+                4 => {
+                    let i: usize = rng.below(model.len() + 2);
+                    assert_eq!(
+                        vec.get(i).map(|elem| elem.value()),
+                        model.get(i).map(|elem| elem.value()),
+                        "seed {}, step {}", seed, step,
+                    );
+                },
+                5 => {
+                    let i: usize = rng.below(model.len() + 2);
+                    match (vec.get_mut(i), model.get_mut(i)) {
+                        (Some(elem), Some(model_elem)) => {
+                            elem.bump();
+                            model_elem.bump();
+                        },
+                        (None, None) => (),
+                        _ => panic!("seed {}, step {}: get_mut disagrees", seed, step),
+                    }
+                },
+                6 => {
+                    for elem in &mut vec {
+                        elem.bump();
+                    }
+                    for elem in &mut model {
+                        elem.bump();
+                    }
+                },
+                7 => if !model.is_empty() {
+                    let i: usize = rng.below(model.len());
+                    vec.remove(i);
+                    model.remove(i);
+                },
+                8 => {
+                    vec.defragment();
+                },
+                _ => {
+                    // the boxes become the new model, and the vector is
+                    // rebuilt from them
+                    let boxes = take(&mut vec).into_box_vec();
+                    check(&vec, &[], seed, step);
+                    for (i, elem) in boxes.iter().enumerate() {
+                        assert_eq!(
+                            elem.value(), model[i].value(),
+                            "seed {}, step {}, index {}", seed, step, i,
+                        );
+                        elem.push_copy(&mut vec, &counter);
+                    }
+                    model = boxes;
+                },
+            }
+            check(&vec, &model, seed, step);
+        }
+
+        drop(vec);
+        drop(model);
+        counter.check();
+    }
+
+    #[test]
+    fn model_random() {
+        // miri is slow, so it gets fewer, shorter runs
+        let (seeds, steps) = if cfg!(miri) { (4, 100) } else { (32, 500) };
+        for seed in 0..seeds {
+            run(seed, steps);
+        }
+    }
+}